@@ -8,7 +8,9 @@ use xrl::{
     Update, ScrollTo, Style,
     AvailablePlugins, UpdateCmds,
     PluginStarted, PluginStoped,
-    ConfigChanged, ThemeChanged,
+    ConfigChanged, ThemeChanged, MeasureWidth,
+    AvailableLanguages, AvailableThemes, FindStatusChanged, ReplaceStatusChanged,
+    LanguageChanged, Alert,
     Client, ServerResult, Frontend,
     FrontendBuilder, spawn,
 };
@@ -59,6 +61,34 @@ impl Frontend for MyFrontend {
         println!("received `theme_changed` from Xi core:\n{:?}", style);
         Box::new(future::ok(()))
     }
+    fn measure_width(&mut self, request: MeasureWidth) -> ServerResult<Vec<Vec<f32>>> {
+        println!("received `measure_width` from Xi core:\n{:?}", request);
+        Box::new(future::ok(Vec::new()))
+    }
+    fn available_languages(&mut self, languages: AvailableLanguages) -> ServerResult<()> {
+        println!("received `available_languages` from Xi core:\n{:?}", languages);
+        Box::new(future::ok(()))
+    }
+    fn available_themes(&mut self, themes: AvailableThemes) -> ServerResult<()> {
+        println!("received `available_themes` from Xi core:\n{:?}", themes);
+        Box::new(future::ok(()))
+    }
+    fn find_status(&mut self, status: FindStatusChanged) -> ServerResult<()> {
+        println!("received `find_status` from Xi core:\n{:?}", status);
+        Box::new(future::ok(()))
+    }
+    fn replace_status(&mut self, status: ReplaceStatusChanged) -> ServerResult<()> {
+        println!("received `replace_status` from Xi core:\n{:?}", status);
+        Box::new(future::ok(()))
+    }
+    fn language_changed(&mut self, language: LanguageChanged) -> ServerResult<()> {
+        println!("received `language_changed` from Xi core:\n{:?}", language);
+        Box::new(future::ok(()))
+    }
+    fn alert(&mut self, alert: Alert) -> ServerResult<()> {
+        println!("received `alert` from Xi core:\n{:?}", alert);
+        Box::new(future::ok(()))
+    }
 }
 
 struct MyFrontendBuilder;
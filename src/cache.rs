@@ -1,4 +1,7 @@
-use {Line, Operation, OperationType, Update};
+use {Line, Operation, OperationType, Position, SelRegion, Update};
+
+/// Id of the style the core uses for the selections.
+const SELECTION_STYLE_ID: u64 = 0;
 
 /// Line cache struct to work with xi update protocol.
 #[derive(Clone, Debug, Default)]
@@ -27,6 +30,19 @@ impl LineCache {
         &self.lines
     }
 
+    /// Retrieve the number of lines of the document, valid or not.
+    pub fn nb_lines(&self) -> u64 {
+        self.invalid_before + self.lines.len() as u64 + self.invalid_after
+    }
+
+    /// Retrieve a line by its number in the document, if it is valid.
+    pub fn line(&self, line_nb: u64) -> Option<&Line> {
+        if line_nb < self.invalid_before {
+            return None;
+        }
+        self.lines.get((line_nb - self.invalid_before) as usize)
+    }
+
     /// Handle an xi-core update.
     pub fn update(&mut self, update: Update) {
         let LineCache {
@@ -45,9 +61,92 @@ impl LineCache {
         helper.update(update.operations);
     }
 
+    /// Retrieve the text between two positions, in any order, if all the
+    /// lines involved are valid.
+    pub fn text(&self, from: &Position, to: &Position) -> Option<String> {
+        let (start, end) = if from <= to { (from, to) } else { (to, from) };
+        let mut text = String::new();
+        for line_nb in start.line..=end.line {
+            let line = &self.line(line_nb)?.text;
+            let first = if line_nb == start.line { start.col as usize } else { 0 };
+            let last = if line_nb == end.line { end.col as usize } else { line.len() };
+            let last = ::std::cmp::min(last, line.len());
+            text.push_str(line.get(::std::cmp::min(first, last)..last)?);
+            if line_nb != end.line {
+                // The lines are stored without their newline.
+                text.push('\n');
+            }
+        }
+        Some(text)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.lines.is_empty()
     }
+
+    /// Retrieve the selections of the valid lines, in document order. They
+    /// are rebuilt from the carets and the spans that use the selection
+    /// style, so a selection that extends beyond the valid lines is
+    /// truncated.
+    pub fn selections(&self) -> Vec<SelRegion> {
+        let mut ranges: Vec<(Position, Position)> = Vec::new();
+        let mut carets: Vec<Position> = Vec::new();
+        // A range that reaches the end of the previous line, and may
+        // continue at the start of the current one.
+        let mut open: Option<(Position, Position)> = None;
+
+        for (idx, line) in self.lines.iter().enumerate() {
+            let line_nb = self.invalid_before + idx as u64;
+            carets.extend(line.cursor.iter().map(|col| Position::new(line_nb, *col)));
+
+            let mut spans = Vec::new();
+            let mut span_end: i64 = 0;
+            for style in &line.styles {
+                let start = ::std::cmp::max(span_end + style.offset, 0);
+                span_end = start + style.length as i64;
+                if style.style_id == SELECTION_STYLE_ID {
+                    spans.push((start as u64, span_end as u64));
+                }
+            }
+
+            if spans.first().map(|&(start, _)| start) != Some(0) {
+                ranges.extend(open.take());
+            }
+            for (start, end) in spans {
+                let range_start = match open.take() {
+                    Some((range_start, _)) => range_start,
+                    None => Position::new(line_nb, start),
+                };
+                let range_end = Position::new(line_nb, end);
+                if end >= line.text.len() as u64 {
+                    open = Some((range_start, range_end));
+                } else {
+                    ranges.push((range_start, range_end));
+                }
+            }
+        }
+        ranges.extend(open.take());
+
+        let mut regions: Vec<SelRegion> = ranges
+            .into_iter()
+            .map(|(start, end)| {
+                if let Some(idx) = carets.iter().position(|caret| *caret == start) {
+                    let _ = carets.remove(idx);
+                    SelRegion::new(end, start)
+                } else {
+                    carets.retain(|caret| *caret != end);
+                    SelRegion::new(start, end)
+                }
+            })
+            .collect();
+        regions.extend(carets.into_iter().map(SelRegion::caret));
+        regions.sort_by(|a, b| {
+            let a = ::std::cmp::min(&a.start, &a.end);
+            let b = ::std::cmp::min(&b.start, &b.end);
+            a.cmp(b)
+        });
+        regions
+    }
 }
 
 struct UpdateHelper<'a, 'b, 'c> {
@@ -217,3 +316,41 @@ fn trim_new_line(text: &mut String) {
         text.pop();
     }
 }
+
+#[test]
+fn selections() {
+    use serde_json;
+
+    let update: Update = serde_json::from_value(json!({
+        "update": {
+            "ops": [
+                {"n": 2, "op": "invalidate"},
+                {"n": 4, "op": "ins", "lines": [
+                    {"text": "foo bar\n", "cursor": [0], "styles": [0, 3, 0, 1, 3, 0]},
+                    {"text": "baz\n", "cursor": [], "styles": [0, 3, 0]},
+                    {"text": "qux\n", "cursor": [2], "styles": [0, 2, 0]},
+                    {"text": "quux\n", "cursor": [1], "styles": []},
+                ]},
+            ],
+            "pristine": true,
+        },
+        "view_id": "view-id-1",
+    })).unwrap();
+    let mut cache = LineCache::default();
+    cache.update(update);
+    assert_eq!(
+        cache.selections(),
+        vec![
+            // selection made backward, from (2, 3) to (2, 0)
+            SelRegion::new(Position::new(2, 3), Position::new(2, 0)),
+            // selection made forward, that spans three lines
+            SelRegion::new(Position::new(2, 4), Position::new(4, 2)),
+            SelRegion::caret(Position::new(5, 1)),
+        ]
+    );
+    assert_eq!(
+        cache.text(&Position::new(4, 2), &Position::new(2, 4)),
+        Some("bar\nbaz\nqu".to_string())
+    );
+    assert_eq!(cache.text(&Position::new(1, 0), &Position::new(2, 3)), None);
+}
@@ -1,337 +1,2058 @@
-use futures::{future, Future};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use futures::{future, stream, Future, Stream};
+use futures::sync::{mpsc, oneshot};
+#[cfg(test)]
+use futures::{executor, Async, Poll};
+use tokio::timer::{Delay, Interval, Timeout};
 use serde_json::Value;
+use serde_json::value::RawValue;
 use errors::ClientError;
 use protocol;
+use protocol::errors::RpcError;
+use protocol::message::Params;
 use serde_json::{from_value, to_value, Map};
 use serde::Serialize;
-use structs::{ModifySelection, ViewId};
+use state::{FindQuery, RequestHandler, SharedState};
+use structs::{
+    ClickModifiers, ClientCapabilities, Config, ConnectionEvent, ConfigDomain, EditEnvelope, EditMethod, EmptyParams, FindMatch, FindStatusChanged, GestureType, SelectionGranularity, SelectionMode, LineEnding, ModifySelection, Position, SelRegion, Style, StyleMap, Symbol, ThemeChanged, ThemeSettings, Update, ViewId, utf16_to_byte_col,
+};
 
-/// A future returned by all the `Client`'s method.
-pub type ClientResult<T> = Box<Future<Item = T, Error = ClientError> + Send>;
+/// A future returned by all the `Client`'s method. It is `Send` and `Sync`,
+/// so it can be spawned on a multi-threaded executor, or stored in a
+/// structure shared between threads.
+pub type ClientResult<T> = Box<Future<Item = T, Error = ClientError> + Send + Sync>;
+
+/// A future returned by the `Client`'s methods that send notifications.
+/// Since the core does not answer notifications, it can only fail because
+/// the message could not be sent.
+pub type NotifyResult = ClientResult<()>;
+
+/// A future returned by the `Client`'s methods that send requests. Besides
+/// the transport errors, it fails with `ClientError::ErrorReturned` if the
+/// core answers with an error.
+pub type RequestResult<T> = ClientResult<T>;
+
+/// Number of lines requested at once by `get_document_text`.
+const DOCUMENT_CHUNK_SIZE: u64 = 1000;
+
+/// Delay during which the calls to `scroll_throttled` are coalesced.
+const SCROLL_THROTTLE_DELAY: Duration = Duration::from_millis(20);
+
+/// Delay after which a `find_all_cancelable` is sent, if it has not been
+/// superseded or canceled meanwhile.
+const FIND_ALL_DELAY: Duration = Duration::from_millis(100);
 
 /// A client to send notifications and request to xi-core.
+///
+/// Requests and notifications are queued as soon as they are sent, in a
+/// single queue shared by all the clones of the client, and they are written
+/// to the core in that order. The messages sent by one clone therefore reach
+/// the core in program order. Messages sent concurrently by several clones
+/// are interleaved, each being sent as a whole: to keep a sequence of edits
+/// together, send it from a single task. With `OverflowPolicy::Block`, a
+/// message sent while the channel is full is still queued in order: only
+/// its future waits for room.
+///
+/// xi-core has no undo-group markers: each edit command is a separate undo
+/// step, so the methods that send several edits, like `indent_by`, are
+/// undone one edit at a time.
+///
+/// The queue is drained before the connection is torn down: a notification
+/// sent right before the last clone of the client is dropped, or before
+/// `shutdown`, is still written to the core, as long as the future returned
+/// by `spawn` is running.
+///
+/// The client is `Send` and `Sync`: it can be shared between threads behind
+/// an `Arc`, although cloning it is as cheap and avoids the indirection.
+/// The streams it returns are `Send` and `Sync` too.
 #[derive(Clone)]
-pub struct Client(pub protocol::Client);
+pub struct Client {
+    inner: protocol::Client,
+    state: SharedState,
+}
+
+/// Serialize the parameters of an edit command. What a command without
+/// parameters gets depends on `empty`.
+fn edit_command_params<T: Serialize>(
+    method: &str,
+    params: Option<T>,
+    empty: EmptyParams,
+) -> Result<Option<Value>, ClientError> {
+    if let Some(params) = params {
+        return to_value(params).map(Some).map_err(|source| ClientError::SerializationFailed {
+            method: method.to_string(),
+            source,
+        });
+    }
+    let params = match empty {
+        EmptyParams::PerMethod => match EditMethod::from_name(method) {
+            Some(method) if method.takes_object_params() => json!({}),
+            _ => json!([]),
+        },
+        EmptyParams::Array => json!([]),
+        EmptyParams::Object => json!({}),
+        EmptyParams::Omit => return Ok(None),
+    };
+    Ok(Some(params))
+}
 
+/// Return the parameters of the `"edit"` message carrying a command, with
+/// the default envelope.
+#[cfg(test)]
 fn get_edit_params<T: Serialize>(
     view_id: ViewId,
     method: &str,
     params: Option<T>,
 ) -> Result<Value, ClientError> {
-    let params_value = if let Some(params) = params {
-        to_value(params)?
+    let params = edit_command_params(method, params, EmptyParams::PerMethod)?;
+    Ok(EditEnvelope::Nested.wrap(view_id, method, params).1)
+}
+
+/// Return the parameters of an `"edit"` notification for a command sent
+/// without parameters, such as the movements. The envelope is formatted
+/// directly instead of being built as a `Value` and serialized, which
+/// matters for the movements sent on every key press. See the
+/// `bare_edit_params_bench` test.
+fn bare_edit_params(view_id: ViewId, method: EditMethod) -> Box<RawValue> {
+    let params = if method.takes_object_params() { "{}" } else { "[]" };
+    let json = format!(
+        r#"{{"method":"{}","view_id":"{}","params":{}}}"#,
+        method.as_str(),
+        view_id,
+        params
+    );
+    RawValue::from_string(json).expect("invalid edit params")
+}
+
+/// Put some text on the system clipboard. Failing to do so is not an error
+/// for the `copy` or `cut` that triggered it, so it is only logged.
+#[cfg(feature = "clipboard")]
+fn set_system_clipboard(text: &str) {
+    use clipboard::{ClipboardContext, ClipboardProvider};
+
+    let result: Result<(), Box<::std::error::Error>> = ClipboardContext::new()
+        .and_then(|mut clipboard: ClipboardContext| clipboard.set_contents(text.to_string()));
+    if let Err(e) = result {
+        warn!("failed to set the system clipboard: {}", e);
+    }
+}
+
+fn client_started_params(config_dir: Option<&str>, client_extra_dir: Option<&str>) -> Map<String, Value> {
+    let mut params = Map::new();
+    if let Some(path) = config_dir {
+        let _ = params.insert("config_dir".into(), json!(path));
+    }
+    if let Some(path) = client_extra_dir {
+        let _ = params.insert("client_extra_dir".into(), json!(path));
+    }
+    params
+}
+
+/// Return the first and last lines of a viewport of `height` lines
+/// centered on `line`.
+fn centered_range(line: u64, height: u64) -> (u64, u64) {
+    let first_line = line.saturating_sub(height / 2);
+    (first_line, first_line + height)
+}
+
+/// Return the first and last lines of a viewport of `height` lines that
+/// shows the lines from `start` to `end`. A range that fits is centered,
+/// and a range that does not fit is shown from its start.
+fn revealed_range(start: u64, end: u64, height: u64) -> (u64, u64) {
+    let (start, end) = if start <= end { (start, end) } else { (end, start) };
+    let len = end - start + 1;
+    let first_line = if len < height {
+        start.saturating_sub((height - len) / 2)
+    } else {
+        start
+    };
+    (first_line, first_line + height)
+}
+
+fn get_config_params(domain: &ConfigDomain, changes: Value) -> Value {
+    json!({
+        "domain": domain,
+        "changes": changes,
+    })
+}
+
+/// Return where `position` ends up after `text` is inserted at `at`.
+fn shift_position(position: Position, at: &Position, text: &str) -> Position {
+    if position < *at {
+        return position;
+    }
+    let nb_newlines = text.matches('\n').count() as u64;
+    let Position { line, col: column } = position;
+    if line != at.line {
+        return Position::new(line + nb_newlines, column);
+    }
+    match text.rfind('\n') {
+        Some(idx) => Position::new(
+            line + nb_newlines,
+            column - at.col + (text.len() - idx - 1) as u64,
+        ),
+        None => Position::new(line, column + text.len() as u64),
+    }
+}
+
+/// Return the first and last lines covered by some selections. A selection
+/// that ends at the start of a line does not cover that line.
+fn selected_lines(regions: &[SelRegion]) -> Option<(u64, u64)> {
+    regions.iter().fold(None, |range, region| {
+        let (start, end) = if region.start <= region.end {
+            (&region.start, &region.end)
+        } else {
+            (&region.end, &region.start)
+        };
+        let last = if end.col == 0 && end.line > start.line { end.line - 1 } else { end.line };
+        match range {
+            Some((first_line, last_line)) => Some((
+                ::std::cmp::min(first_line, start.line),
+                ::std::cmp::max(last_line, last),
+            )),
+            None => Some((start.line, last)),
+        }
+    })
+}
+
+/// Given lines without their line endings, return the text where the first
+/// line has been moved after the others if `up` is true, or the last line
+/// has been moved before the others otherwise.
+fn swap_lines(lines: &[&str], up: bool) -> String {
+    let mut lines = lines.to_vec();
+    if up {
+        let first = lines.remove(0);
+        lines.push(first);
+    } else if let Some(last) = lines.pop() {
+        lines.insert(0, last);
+    }
+    lines.join("\n")
+}
+
+/// Return the range to delete to remove the lines `first` to `last` of a
+/// document of `nb_lines` lines, given the length of the lines before and
+/// after the range. The newline that follows the lines is removed with
+/// them, or, for the last line of the document, the one that precedes them.
+fn line_deletion_range(
+    first: u64,
+    last: u64,
+    nb_lines: u64,
+    previous_len: Option<u64>,
+    last_len: u64,
+) -> Option<(Position, Position)> {
+    if last + 1 < nb_lines {
+        Some((Position::new(first, 0), Position::new(last + 1, 0)))
+    } else if first == 0 {
+        Some((Position::new(0, 0), Position::new(last, last_len)))
+    } else {
+        previous_len.map(|len| (Position::new(first - 1, len), Position::new(last, last_len)))
+    }
+}
+
+/// Return the command a `*_sel` movement sends in the given
+/// `SelectionMode`: the movement that `extend`s the selections, or the
+/// movement that `add`s a caret, if there is one.
+fn sel_movement(extend: EditMethod, add: Option<EditMethod>, mode: SelectionMode) -> Option<EditMethod> {
+    match mode {
+        SelectionMode::Extend => Some(extend),
+        SelectionMode::AddCaret => add,
+    }
+}
+
+/// Look for an offset in a line, for `offset_to_position`. `acc` holds the
+/// offset relative to the start of the line, and the position found so far.
+/// Return whether the following lines must be looked at.
+fn locate_offset(acc: &mut (u64, (u64, u64)), line_nb: u64, text: &str) -> bool {
+    let len = text.len() as u64;
+    if acc.0 < len || !text.ends_with('\n') {
+        acc.1 = (line_nb, ::std::cmp::min(acc.0, len));
+        return acc.0 >= len;
+    }
+    acc.0 -= len;
+    acc.1 = (line_nb + 1, 0);
+    true
+}
+
+/// Split `text` into pieces of at most `size` bytes, on character
+/// boundaries. A character longer than `size` is put in a piece on its own.
+fn split_chunks(text: &str, size: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = ::std::cmp::min(size, rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map(char::len_utf8).unwrap_or(rest.len());
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Return the matches of a `"find_status"` notification that have not been
+/// reported yet. The core reports the line of every match of each query in
+/// each notification, so `reported` holds the number of matches of each
+/// query already reported. If a query has fewer matches than that, it
+/// changed, and all its matches are reported again.
+fn new_find_matches(reported: &mut HashMap<u64, usize>, status: &FindStatusChanged) -> Vec<FindMatch> {
+    let mut matches = Vec::new();
+    for query in &status.queries {
+        let count = reported.entry(query.id).or_insert(0);
+        if query.lines.len() < *count {
+            *count = 0;
+        }
+        matches.extend(query.lines[*count..].iter().map(|&line| FindMatch {
+            query_id: query.id,
+            line,
+        }));
+        *count = query.lines.len();
+    }
+    matches
+}
+
+/// Return the delimiters of the block comments of a language, as named by
+/// the core, if it has block comments.
+fn block_comment_delimiters(language: &str) -> Option<(&'static str, &'static str)> {
+    match language {
+        "C" | "C++" | "C#" | "CSS" | "Go" | "Java" | "JavaScript" | "Objective-C" | "PHP"
+        | "Rust" | "Scala" | "Swift" | "TypeScript" => Some(("/*", "*/")),
+        "HTML" | "Markdown" | "XML" => Some(("<!--", "-->")),
+        _ => None,
+    }
+}
+
+/// Wrap `text` in a block comment, or remove the block comment that wraps
+/// it.
+fn toggle_block_comment_text(text: &str, open: &str, close: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.len() >= open.len() + close.len() && trimmed.starts_with(open) && trimmed.ends_with(close) {
+        let start = text.find(open).unwrap();
+        let end = text.rfind(close).unwrap();
+        let inner = &text[start + open.len()..end];
+        let inner = inner.strip_prefix(' ').unwrap_or(inner);
+        let inner = inner.strip_suffix(' ').unwrap_or(inner);
+        format!("{}{}{}", &text[..start], inner, &text[end + close.len()..])
     } else {
-        json!([])
+        format!("{} {} {}", open, text, close)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Return the byte columns of the start and end of the word at `col` in
+/// `text`. A column right after a word, where a caret typically is after
+/// typing the word, is in that word. Words are made of alphanumeric
+/// characters and underscores.
+fn word_bounds(text: &str, col: usize) -> Option<(usize, usize)> {
+    if !text.is_char_boundary(col) {
+        return None;
+    }
+    let starts_word = match text[col..].chars().next() {
+        Some(c) => is_word_char(c),
+        None => false,
     };
+    let ends_word = match text[..col].chars().next_back() {
+        Some(c) => is_word_char(c),
+        None => false,
+    };
+    if !starts_word && !ends_word {
+        return None;
+    }
+    let start = text[..col]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word_char(c))
+        .last()
+        .map_or(col, |(idx, _)| idx);
+    let end = text[col..]
+        .char_indices()
+        .find(|&(_, c)| !is_word_char(c))
+        .map_or(text.len(), |(idx, _)| col + idx);
+    Some((start, end))
+}
+
+/// The kinds of characters that delimit subwords.
+#[derive(PartialEq, Clone, Copy)]
+enum CharClass {
+    Separator,
+    Lower,
+    Upper,
+    Digit,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() || c == '_' {
+        CharClass::Separator
+    } else if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else {
+        CharClass::Other
+    }
+}
+
+/// Return the byte column of the end of the subword that follows `col` in
+/// `text`. Subwords are delimited by whitespace, underscores and case
+/// changes: `"fooBar"` has two subwords, as do `"foo_bar"` and
+/// `"HTTPServer"`.
+fn next_subword_boundary(text: &str, col: usize) -> usize {
+    let chars: Vec<(usize, char)> = text[col..]
+        .char_indices()
+        .map(|(idx, c)| (idx + col, c))
+        .collect();
+    let class_at = |i: usize| char_class(chars[i].1);
+    let mut i = 0;
+    while i < chars.len() && class_at(i) == CharClass::Separator {
+        i += 1;
+    }
+    if i < chars.len() {
+        let class = class_at(i);
+        let start = i;
+        while i < chars.len() && class_at(i) == class {
+            i += 1;
+        }
+        if class == CharClass::Upper {
+            if i - start == 1 {
+                // A capitalized subword, such as "Bar" in "fooBar".
+                while i < chars.len() && class_at(i) == CharClass::Lower {
+                    i += 1;
+                }
+            } else if i < chars.len() && class_at(i) == CharClass::Lower {
+                // The last capital starts the next subword, as "S" in
+                // "HTTPServer".
+                i -= 1;
+            }
+        }
+    }
+    chars.get(i).map(|&(idx, _)| idx).unwrap_or_else(|| text.len())
+}
 
-    Ok(json!({
-        "method": method,
-        "view_id": view_id,
-        "params": params_value,
-    }))
+/// Return the byte column of the start of the subword that precedes `col`
+/// in `text`. See `next_subword_boundary`.
+fn prev_subword_boundary(text: &str, col: usize) -> usize {
+    let chars: Vec<(usize, char)> = text[..col].char_indices().collect();
+    let class_at = |i: usize| char_class(chars[i].1);
+    let mut i = chars.len();
+    while i > 0 && class_at(i - 1) == CharClass::Separator {
+        i -= 1;
+    }
+    if i > 0 {
+        let class = class_at(i - 1);
+        while i > 0 && class_at(i - 1) == class {
+            i -= 1;
+        }
+        if class == CharClass::Lower && i > 0 && class_at(i - 1) == CharClass::Upper {
+            i -= 1;
+        }
+    }
+    chars.get(i).map(|&(idx, _)| idx).unwrap_or(col)
 }
 
+/// Return the position `delta` characters after `position`, or before it
+/// if `delta` is negative, going to the next or previous line past the end
+/// or the start of a line. The motion stops at the end or the start of
+/// the document, and at lines for which `text` returns `None`.
+fn shift_column<'a, F: Fn(u64) -> Option<&'a str>>(text: F, position: Position, delta: i64) -> Position {
+    let Position { mut line, col } = position;
+    let mut col = col as usize;
+    for _ in 0..delta.unsigned_abs() {
+        let current = match text(line) {
+            Some(current) => current,
+            None => break,
+        };
+        col = ::std::cmp::min(col, current.len());
+        if delta > 0 {
+            match current[col..].chars().next() {
+                Some(c) => col += c.len_utf8(),
+                None => match text(line + 1) {
+                    Some(_) => {
+                        line += 1;
+                        col = 0;
+                    }
+                    None => break,
+                },
+            }
+        } else {
+            match current[..col].chars().next_back() {
+                Some(c) => col -= c.len_utf8(),
+                None => match line.checked_sub(1).and_then(&text) {
+                    Some(previous) => {
+                        line -= 1;
+                        col = previous.len();
+                    }
+                    None => break,
+                },
+            }
+        }
+    }
+    Position::new(line, col as u64)
+}
 
 impl Client {
+    pub(crate) fn new(inner: protocol::Client) -> Self {
+        Client {
+            inner,
+            state: SharedState::default(),
+        }
+    }
+
+    pub(crate) fn state(&self) -> SharedState {
+        self.state.clone()
+    }
+
+    /// Return the style with the given id, if the core defined it.
+    pub fn style(&self, style_id: u64) -> Option<Style> {
+        self.state.lock().unwrap().styles.get(&style_id).cloned()
+    }
+
+    /// Return all the styles defined by the core so far.
+    pub fn style_map(&self) -> StyleMap {
+        self.state.lock().unwrap().styles.clone()
+    }
+
+    /// Return a future that resolves with the next `"update"` received for
+    /// the given view. The waiter is registered immediately, so an update
+    /// that arrives before the future is polled is not missed.
+    fn next_update(&self, view_id: ViewId) -> ClientResult<Update> {
+        let (tx, rx) = oneshot::channel();
+        self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .update_waiters
+            .push(tx);
+        Box::new(rx.map_err(|_| ClientError::RequestFailed))
+    }
+
+    /// Enable or disable the strict mode, for this client and all its
+    /// clones. In strict mode, the client keeps track of the views opened
+    /// with `new_view` and closed with `close_view`, and the methods that
+    /// take a `ViewId` fail with `ClientError::UnknownView` without sending
+    /// anything if the view is not open. This is meant to catch bugs such
+    /// as using a view after closing it during development.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.state.lock().unwrap().strict = strict;
+    }
+
+    /// Retrieve the views opened with `new_view` that have not been closed
+    /// yet with `close_view`. They are sorted by id, which for xi-core is
+    /// the order in which they have been opened.
+    pub fn open_views(&self) -> Vec<ViewId> {
+        let mut views: Vec<ViewId> = self.state
+            .lock()
+            .unwrap()
+            .open_views
+            .iter()
+            .cloned()
+            .collect();
+        views.sort_by_key(|view_id| view_id.0);
+        views
+    }
+
+    /// In strict mode, check that a view is open.
+    fn check_view(&self, view_id: ViewId) -> Result<(), ClientError> {
+        let state = self.state.lock().unwrap();
+        if state.strict && !state.open_views.contains(&view_id) {
+            Err(ClientError::UnknownView(view_id))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Send a notification to the core. Most (if not all) notifications
     /// supported by the core are already implemented, so this method
     /// should not be necessary in most cases.
-    pub fn notify(&mut self, method: &str, params: Value) -> ClientResult<()> {
+    pub fn notify(&mut self, method: &str, params: Value) -> NotifyResult {
         info!(">>> notification: method={}, params={}", method, &params);
+        self.send_notification(method, params)
+    }
+
+    fn send_notification<P: Into<Params>>(&mut self, method: &str, params: P) -> NotifyResult {
         Box::new(
-            self.0
+            self.inner
                 .notify(method, params)
-                .map_err(|_| ClientError::NotifyFailed),
+                .map_err(|e| match e {
+                    RpcError::ChannelFull => ClientError::ChannelFull,
+                    _ => ClientError::NotifyFailed,
+                }),
         )
     }
 
     /// Send a request to the core. Most (if not all) notifications
     /// supported by the core are already implemented, so this method
     /// should not be necessary in most cases.
-    pub fn request(&mut self, method: &str, params: Value) -> ClientResult<Value> {
+    ///
+    /// Several requests can be in flight at the same time: each response is
+    /// matched to its request by id, whatever the order in which the core
+    /// answers.
+    pub fn request(&mut self, method: &str, params: Value) -> RequestResult<Value> {
         info!(">>> request : method={}, params={}", method, &params);
-        Box::new(self.0.request(method, params).then(
+        self.send_request(method, params)
+    }
+
+    /// Send a notification with parameters that are already serialized.
+    /// They are written as is, which avoids converting them to a `Value`
+    /// and back, for instance when replaying recorded messages.
+    pub fn notify_raw(&mut self, method: &str, params: Box<RawValue>) -> NotifyResult {
+        info!(">>> notification: method={}, params={}", method, params.get());
+        self.send_notification(method, params)
+    }
+
+    /// Send a request with parameters that are already serialized. See
+    /// `notify_raw`.
+    pub fn request_raw(&mut self, method: &str, params: Box<RawValue>) -> RequestResult<Value> {
+        info!(">>> request : method={}, params={}", method, params.get());
+        self.send_request(method, params)
+    }
+
+    fn send_request<P: Into<Params>>(&mut self, method: &str, params: P) -> RequestResult<Value> {
+        Box::new(self.inner.request(method, params).then(
             |response| match response {
                 Ok(Ok(value)) => Ok(value),
                 Ok(Err(value)) => Err(ClientError::ErrorReturned(value)),
+                Err(RpcError::ChannelFull) => Err(ClientError::ChannelFull),
                 Err(_) => Err(ClientError::RequestFailed),
             },
         ))
     }
 
-    pub fn edit_request<T: Serialize>(
+    pub fn edit_request<M: AsRef<str>, T: Serialize>(
         &mut self,
         view_id: ViewId,
-        method: &str,
-        params: Option<T>)-> ClientResult<Value> {
-            match get_edit_params(view_id, method, params) {
-                Ok(value) => self.request("edit", value),
+        method: M,
+        params: Option<T>)-> RequestResult<Value> {
+            match self.check_view(view_id).and_then(|_| self.wrap_edit(view_id, method.as_ref(), params)) {
+                Ok((rpc_method, value)) => self.request(&rpc_method, value),
                 Err(e) => Box::new(future::err(e)),
         }
     }
 
+    /// Return the method and the parameters of the message carrying an
+    /// edit command, wrapped as set with `set_edit_envelope`.
+    fn wrap_edit<T: Serialize>(
+        &self,
+        view_id: ViewId,
+        method: &str,
+        params: Option<T>,
+    ) -> Result<(String, Value), ClientError> {
+        let (envelope, empty) = {
+            let state = self.state.lock().unwrap();
+            (state.edit_envelope, state.empty_params)
+        };
+        edit_command_params(method, params, empty).map(|params| envelope.wrap(view_id, method, params))
+    }
+
+    /// Set what is sent as the parameters of the edit commands that are
+    /// given none, for a core that is stricter than xi-core about them. By
+    /// default, an empty object or array is sent, depending on what xi-core
+    /// expects for the command.
+    pub fn set_empty_params(&self, empty: EmptyParams) {
+        self.state.lock().unwrap().empty_params = empty;
+    }
+
+    /// Return how the edit commands are wrapped in the messages sent to
+    /// the core.
+    pub fn edit_envelope(&self) -> EditEnvelope {
+        self.state.lock().unwrap().edit_envelope
+    }
+
+    /// Set how the edit commands are wrapped in the messages sent to the
+    /// core, for a core that does not expect them in `"edit"` messages.
+    pub fn set_edit_envelope(&self, envelope: EditEnvelope) {
+        self.state.lock().unwrap().edit_envelope = envelope;
+    }
+
     /// Send an "edit" notification. Most (if not all) "edit" commands are
     /// already implemented, so this method should not be necessary in most
     /// cases.
-    pub fn edit_notify<T: Serialize>(
+    ///
+    /// The method can be given as an `EditMethod`, or as a string for the
+    /// commands that `EditMethod` does not know about.
+    pub fn edit_notify<M: AsRef<str>, T: Serialize>(
         &mut self,
         view_id: ViewId,
-        method: &str,
+        method: M,
         params: Option<T>,
-    ) -> ClientResult<()> {
-        match get_edit_params(view_id, method, params) {
-            Ok(value) => self.notify("edit", value),
+    ) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let default_wrapping = {
+            let state = self.state.lock().unwrap();
+            state.edit_envelope.is_nested() && state.empty_params == EmptyParams::PerMethod
+        };
+        if params.is_none() && default_wrapping {
+            if let Some(method) = EditMethod::from_name(method.as_ref()) {
+                return self.notify_raw("edit", bare_edit_params(view_id, method));
+            }
+        }
+        match self.wrap_edit(view_id, method.as_ref(), params) {
+            Ok((rpc_method, value)) => self.notify(&rpc_method, value),
             Err(e) => Box::new(future::err(e)),
         }
     }
 
+    /// Send an "edit" notification, and resolve with the next update the
+    /// core sends for the view. The future waits for the update from the
+    /// moment this method is called, so an update that arrives before the
+    /// future is polled is not missed.
+    pub fn edit_then_update<M: AsRef<str>, T: Serialize>(
+        &mut self,
+        view_id: ViewId,
+        method: M,
+        params: Option<T>,
+    ) -> ClientResult<Update> {
+        let update = self.next_update(view_id);
+        Box::new(self.edit_notify(view_id, method, params).and_then(|_| update))
+    }
+
     /// Send an "scroll" notification
     /// ```ignore
     /// {"method":"edit","params":{"method":"scroll","params":[21,80],
     /// "view_id":"view-id-1"}}
     /// ```
-    pub fn scroll(&mut self, view_id: ViewId, first_line: u64, last_line: u64) -> ClientResult<()> {
-        self.edit_notify(view_id, "scroll", Some(json!([first_line, last_line])))
-    }
-
-    pub fn goto_line(&mut self, view_id: ViewId, line: u64) -> ClientResult<()> {
-        self.edit_notify(view_id, "goto_line", Some(json!({"line": line})))
-    }
-
-    pub fn copy(&mut self, view_id: ViewId) -> ClientResult<Value> {
-        self.edit_request(view_id, "copy", None as Option<Value>)
+    pub fn scroll(&mut self, view_id: ViewId, first_line: u64, last_line: u64) -> NotifyResult {
+        {
+            let mut state = self.state.lock().unwrap();
+            let view = state.view_mut(view_id);
+            // This scroll supersedes the ones that are being throttled.
+            let _ = view.pending_scroll.take();
+            view.fractional_scroll = None;
+        }
+        self.edit_notify(view_id, EditMethod::Scroll, Some(json!([first_line, last_line])))
     }
 
-    pub fn paste(&mut self, view_id: ViewId, buffer: &str) -> ClientResult<()> {
-        self.edit_notify(view_id, "paste", Some(json!({"chars": buffer})))
+    /// Like `scroll`, but the calls made for a view during
+    /// `SCROLL_THROTTLE_DELAY` are coalesced, and only the last range is
+    /// sent to the core once the delay has elapsed. This is meant for fast
+    /// scrolling, where a frontend could send a `"scroll"` for each frame
+    /// whereas the core only needs the final viewport.
+    ///
+    /// The range is sent by the first returned future that completes its
+    /// delay, so at least one of them must be polled.
+    pub fn scroll_throttled(
+        &mut self,
+        view_id: ViewId,
+        first_line: u64,
+        last_line: u64,
+    ) -> NotifyResult {
+        self.state.lock().unwrap().view_mut(view_id).pending_scroll =
+            Some((first_line, last_line));
+        let mut client = self.clone();
+        let result = Delay::new(Instant::now() + SCROLL_THROTTLE_DELAY)
+            .map_err(|_| ClientError::NotifyFailed)
+            .and_then(move |_| {
+                let range = client.state.lock().unwrap().view_mut(view_id).pending_scroll.take();
+                match range {
+                    Some((first_line, last_line)) => client.scroll(view_id, first_line, last_line),
+                    None => Box::new(future::ok(())),
+                }
+            });
+        Box::new(result)
     }
 
-    pub fn cut(&mut self, view_id: ViewId) -> ClientResult<Value> {
-        self.edit_request(view_id, "cut", None as Option<Value>)
+    /// Scroll to a fractional first line, for frontends that scroll
+    /// smoothly. The core only knows about whole lines, so `first_line` is
+    /// rounded to the nearest line, and the `viewport_lines` lines that
+    /// follow are requested, plus one for the partially visible line at
+    /// the bottom. Since the position is absolute, the small scrolls add up
+    /// instead of being lost to rounding. Nothing is sent if the range did
+    /// not change since the last call; use `scroll_remainder` to get the
+    /// offset at which to draw the lines.
+    pub fn scroll_fractional(
+        &mut self,
+        view_id: ViewId,
+        first_line: f64,
+        viewport_lines: u64,
+    ) -> NotifyResult {
+        let first_line = first_line.max(0.0);
+        let start = first_line.round() as u64;
+        let range = (start, start + viewport_lines + 1);
+        let remainder = first_line - start as f64;
+        {
+            let mut state = self.state.lock().unwrap();
+            let view = state.view_mut(view_id);
+            if view.fractional_scroll.map(|(previous, _)| previous) == Some(range) {
+                view.fractional_scroll = Some((range, remainder));
+                return Box::new(future::ok(()));
+            }
+        }
+        let result = self.scroll(view_id, range.0, range.1);
+        self.state.lock().unwrap().view_mut(view_id).fractional_scroll = Some((range, remainder));
+        result
     }
 
-    pub fn undo(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "undo", None as Option<Value>)
+    /// Return the difference between the fractional first line given to
+    /// the last `scroll_fractional` and the line the view was scrolled to,
+    /// between -0.5 and 0.5.
+    pub fn scroll_remainder(&self, view_id: ViewId) -> f64 {
+        self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .fractional_scroll
+            .map(|(_, remainder)| remainder)
+            .unwrap_or(0.0)
     }
 
-    pub fn redo(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "redo", None as Option<Value>)
+    /// Select the whole document, and resolve with the selected region once
+    /// the core has sent the corresponding update. The region is taken from
+    /// the `"selection"` annotation of the update. Older versions of the
+    /// core do not send annotations: the last line of the document is then
+    /// fetched with `fetch_lines` to know where the selection ends, unless
+    /// it is already in the line cache. If the cached selection already
+    /// covers the whole document, the core would not send any update, so
+    /// the future resolves right away.
+    pub fn select_all_confirmed(&mut self, view_id: ViewId) -> ClientResult<SelRegion> {
+        let selected = {
+            let mut state = self.state.lock().unwrap();
+            let cache = &state.view_mut(view_id).cache;
+            let last_line = cache.nb_lines().saturating_sub(1);
+            cache.line(last_line).and_then(|line| {
+                let column = line.text.trim_end_matches('\n').len() as u64;
+                let whole = SelRegion::new(Position::new(0, 0), Position::new(last_line, column));
+                if cache.line(0).is_some() && cache.selections() == vec![whole.clone()] {
+                    Some(whole)
+                } else {
+                    None
+                }
+            })
+        };
+        if let Some(region) = selected {
+            return Box::new(future::ok(region));
+        }
+        let mut client = self.clone();
+        let result = self.edit_then_update(view_id, EditMethod::SelectAll, None as Option<Value>)
+            .and_then(move |update| {
+                let annotated = update
+                    .annotations
+                    .iter()
+                    .filter(|annotation| annotation.ty == "selection")
+                    .flat_map(|annotation| annotation.ranges.iter())
+                    .map(|&(start, end)| SelRegion::new(start, end))
+                    .next();
+                if let Some(region) = annotated {
+                    return Box::new(future::ok(region)) as ClientResult<SelRegion>;
+                }
+                let last_line = {
+                    let mut state = client.state.lock().unwrap();
+                    state.view_mut(view_id).cache.nb_lines().saturating_sub(1)
+                };
+                let state = client.state.clone();
+                let region = client.fetch_lines(view_id, last_line, last_line + 1)
+                    .map(move |_| {
+                        let mut state = state.lock().unwrap();
+                        let column = state
+                            .view_mut(view_id)
+                            .cache
+                            .line(last_line)
+                            .map(|line| line.text.trim_end_matches('\n').len() as u64)
+                            .unwrap_or(0);
+                        SelRegion::new(Position::new(0, 0), Position::new(last_line, column))
+                    });
+                Box::new(region)
+            });
+        Box::new(result)
     }
 
-    pub fn find(
+    /// Scroll so that `line` is in the middle of a viewport of
+    /// `viewport_height` lines, like `zz` in vim. Near the top of the
+    /// document, the viewport starts at the first line instead.
+    pub fn center_line(
         &mut self,
         view_id: ViewId,
-        search_term: &str,
-        case_sensitive: bool,
-        regex: bool,
-        whole_words: bool,
-    ) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "find",
-            Some(json!({
-                "chars": search_term,
-                "case_sensitive": case_sensitive,
-                "regex": regex,
-                "whole_words": whole_words}))
-        )
+        line: u64,
+        viewport_height: u64,
+    ) -> NotifyResult {
+        let (first_line, last_line) = centered_range(line, viewport_height);
+        self.scroll(view_id, first_line, last_line)
     }
 
-    fn find_other(
+    /// Scroll the view so that the lines from `start_line` to `end_line`,
+    /// such as the lines of a selection, are visible in a viewport of
+    /// `viewport_height` lines. If the lines do not all fit, the first ones
+    /// are shown.
+    pub fn reveal_range(
         &mut self,
         view_id: ViewId,
-        command: &str,
-        wrap_around: bool,
-        allow_same: bool,
-        modify_selection: ModifySelection,
-    ) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            command,
-            Some(json!({
-                "wrap_around": wrap_around,
-                "allow_same": allow_same,
-                "modify_selection": modify_selection}))
-        )
+        start_line: u64,
+        end_line: u64,
+        viewport_height: u64,
+    ) -> NotifyResult {
+        let (first_line, last_line) = revealed_range(start_line, end_line, viewport_height);
+        self.scroll(view_id, first_line, last_line)
     }
 
-    pub fn find_next(
+    /// Send a `"request_lines"` notification, asking the core to send the
+    /// lines between `first_line` and `last_line` in an update.
+    pub fn request_lines(
         &mut self,
         view_id: ViewId,
-        wrap_around: bool,
-        allow_same: bool,
-        modify_selection: ModifySelection,
-    ) -> ClientResult<()> {
-        self.find_other(
-            view_id,
-            "find_next",
-            wrap_around,
-            allow_same,
-            modify_selection)
+        first_line: u64,
+        last_line: u64,
+    ) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::RequestLines, Some(json!([first_line, last_line])))
     }
 
-    pub fn find_prev(
-        &mut self,
-        view_id: ViewId,
-        wrap_around: bool,
-        allow_same: bool,
-        modify_selection: ModifySelection,
-    ) -> ClientResult<()> {
-        self.find_other(
-            view_id,
-            "find_previous",
-            wrap_around,
-            allow_same,
-            modify_selection)
+    /// Return the number of lines of a view, as known from the last update
+    /// received from the core. If no update has been received for the view
+    /// yet, the future resolves once the first one arrives.
+    pub fn line_count(&mut self, view_id: ViewId) -> ClientResult<u64> {
+        let mut state = self.state.lock().unwrap();
+        let view = state.view_mut(view_id);
+        let nb_lines = view.cache.nb_lines();
+        if nb_lines != 0 {
+            return Box::new(future::ok(nb_lines));
+        }
+        // Register the waiter under the same lock, so that an update that
+        // arrives in between is not missed.
+        let (tx, rx) = oneshot::channel();
+        view.update_waiters.push(tx);
+        let state = self.state.clone();
+        let result = rx.map_err(|_| ClientError::RequestFailed).map(move |update| {
+            state.lock().unwrap().view_mut(update.view_id).cache.nb_lines()
+        });
+        Box::new(result)
     }
 
-    pub fn find_all(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "find_all", None as Option<Value>)
+    /// Make sure that the lines `first..last` of a view are in the line
+    /// cache. xi-core only sends the lines it has not sent already, so
+    /// waiting for an update after requesting lines that are cached would
+    /// never resolve: only the range between the first and the last missing
+    /// lines is requested, and the future then resolves once the next
+    /// update has been received. Otherwise it resolves right away. The
+    /// range is limited to the size of the document, which is only known
+    /// once the first update has been received, as with `line_count`.
+    fn fetch_lines(&mut self, view_id: ViewId, first: u64, last: u64) -> ClientResult<()> {
+        let mut client = self.clone();
+        let result = self.line_count(view_id).and_then(move |nb_lines| {
+            let missing = {
+                let mut state = client.state.lock().unwrap();
+                let cache = &state.view_mut(view_id).cache;
+                let mut missing = (first..::std::cmp::min(last, nb_lines)).filter(|n| cache.line(*n).is_none());
+                missing.next().map(|start| (start, missing.next_back().unwrap_or(start) + 1))
+            };
+            match missing {
+                Some((start, end)) => {
+                    let update = client.next_update(view_id);
+                    let fetched = client.request_lines(view_id, start, end).and_then(|_| update);
+                    Box::new(fetched.map(|_| ())) as ClientResult<()>
+                }
+                None => Box::new(future::ok(())),
+            }
+        });
+        Box::new(result)
     }
 
-    pub fn highlight_find(&mut self, view_id: ViewId, visible: bool) -> ClientResult<()> {
-        self.edit_notify(view_id, "highlight_find", Some(json!({"visible": visible})))
+    /// Retrieve the text of a single line of a view, without its trailing
+    /// newline. The line is taken from the line cache, or fetched with
+    /// `fetch_lines` if it is missing. It fails with
+    /// `ClientError::LineOutOfRange` if the line is past the end of the
+    /// document, so that an empty line can be told apart from a line that
+    /// does not exist.
+    pub fn get_line(&mut self, view_id: ViewId, line: u64) -> ClientResult<String> {
+        let nb_lines = self.state.lock().unwrap().view_mut(view_id).cache.nb_lines();
+        // Before the first update, the size of the document is not known.
+        if nb_lines != 0 && line >= nb_lines {
+            return Box::new(future::err(ClientError::LineOutOfRange(line)));
+        }
+        let state = self.state.clone();
+        let result = self.fetch_lines(view_id, line, line + 1)
+            .and_then(move |_| {
+                let mut state = state.lock().unwrap();
+                match state.view_mut(view_id).cache.line(line) {
+                    Some(l) => Ok(l.text.trim_end_matches('\n').to_string()),
+                    None => Err(ClientError::LineOutOfRange(line)),
+                }
+            });
+        Box::new(result)
     }
 
-    pub fn left(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "move_left", None as Option<Value>)
+    /// Return the word at the given position, along with the columns of its
+    /// start and end, for instance to look up the word under the caret. A
+    /// position right after a word is in that word. The line is fetched
+    /// with `get_line`, and the future resolves with `None` if there is no
+    /// word at this position.
+    pub fn word_at(&mut self, view_id: ViewId, line: u64, col: u64) -> ClientResult<Option<(String, u64, u64)>> {
+        let result = self.get_line(view_id, line).map(move |text| {
+            word_bounds(&text, col as usize)
+                .map(|(start, end)| (text[start..end].to_string(), start as u64, end as u64))
+        });
+        Box::new(result)
     }
 
-    pub fn left_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "move_left_and_modify_selection",
-            None as Option<Value>,
-        )
+    /// Retrieve the content of a view. This is meant to be used in tests:
+    /// the lines of the view missing from the client's line cache are
+    /// fetched with `fetch_lines`, and the future resolves with the content
+    /// of the line cache. Lines are joined with `'\n'`, and lines the core
+    /// did not send are left out.
+    pub fn debug_get_contents(&mut self, view_id: ViewId) -> ClientResult<String> {
+        let state = self.state.clone();
+        let mut client = self.clone();
+        let result = self.line_count(view_id)
+            .and_then(move |nb_lines| client.fetch_lines(view_id, 0, nb_lines))
+            .map(move |_| {
+                let mut state = state.lock().unwrap();
+                let lines = state.view_mut(view_id).cache.lines();
+                lines
+                    .iter()
+                    .map(|line| line.text.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+            });
+        Box::new(result)
     }
 
-    pub fn right(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "move_right", None as Option<Value>)
+    /// Go through the lines of a view by chunks of `DOCUMENT_CHUNK_SIZE`
+    /// lines, fetching the ones missing from the line cache with
+    /// `fetch_lines`, and call `f` with the number and the text of each
+    /// line, in order. The line cache stores the lines without their
+    /// newline, so it is added back to all the lines but the last one. The
+    /// scan stops when `f` returns `false`, or at the end of the document,
+    /// and the future resolves with the accumulator `acc`.
+    fn scan_lines<T, F>(&mut self, view_id: ViewId, acc: T, f: F) -> ClientResult<T>
+    where
+        T: Send + Sync + 'static,
+        F: FnMut(&mut T, u64, &str) -> bool + Send + Sync + 'static,
+    {
+        let init = (self.clone(), acc, f, 0);
+        let result = future::loop_fn(init, move |(mut client, mut acc, mut f, first)| {
+            let last = first + DOCUMENT_CHUNK_SIZE;
+            client.fetch_lines(view_id, first, last).map(move |_| {
+                let (nb_lines, done) = {
+                    let mut state = client.state.lock().unwrap();
+                    let cache = &state.view_mut(view_id).cache;
+                    let nb_lines = cache.nb_lines();
+                    let mut done = false;
+                    for line_nb in first..last {
+                        if let Some(line) = cache.line(line_nb) {
+                            let text = if line_nb + 1 < nb_lines {
+                                format!("{}\n", line.text)
+                            } else {
+                                line.text.clone()
+                            };
+                            if !f(&mut acc, line_nb, &text) {
+                                done = true;
+                                break;
+                            }
+                        }
+                    }
+                    (nb_lines, done)
+                };
+                if done || last >= nb_lines {
+                    future::Loop::Break(acc)
+                } else {
+                    future::Loop::Continue((client, acc, f, last))
+                }
+            })
+        });
+        Box::new(result)
     }
 
-    pub fn right_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "move_right_and_modify_selection",
-            None as Option<Value>,
-        )
+    /// Retrieve the whole text of a view, without touching the selections
+    /// or the kill buffer like `select_all` followed by `copy` would. The
+    /// lines missing from the line cache are requested from the core by
+    /// chunks of `DOCUMENT_CHUNK_SIZE` lines, and concatenated as they are
+    /// received.
+    pub fn get_document_text(&mut self, view_id: ViewId) -> ClientResult<String> {
+        self.scan_lines(view_id, String::new(), |text, _, line| {
+            text.push_str(line);
+            true
+        })
     }
 
-    pub fn up(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "move_up", None as Option<Value>)
+    /// Convert a byte offset in the text of a view, as reported by an
+    /// external tool for instance, to a `Position`. The lines
+    /// are requested from the core until the one containing the offset is
+    /// found. An offset past the end of the document is converted to the
+    /// end of the document.
+    pub fn offset_to_position(&mut self, view_id: ViewId, offset: u64) -> ClientResult<Position> {
+        let result = self.scan_lines(view_id, (offset, (0, 0)), |acc, line_nb, line| {
+            locate_offset(acc, line_nb, line)
+        });
+        Box::new(result.map(|(_, (line, col))| Position::new(line, col)))
     }
 
-    pub fn up_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "move_up_and_modify_selection",
-            None as Option<Value>,
-        )
+    /// Mark a view as the focused one. xi-core has no notion of focus, so
+    /// nothing is sent to the core: the focused view is only recorded by
+    /// the client, and can be retrieved with `focused_view`.
+    pub fn focus_view(&mut self, view_id: ViewId) {
+        self.state.lock().unwrap().focused_view = Some(view_id);
     }
 
-    pub fn down(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "move_down", None as Option<Value>)
+    /// Mark a view as not focused anymore. See `focus_view`.
+    pub fn blur_view(&mut self, view_id: ViewId) {
+        let mut state = self.state.lock().unwrap();
+        if state.focused_view == Some(view_id) {
+            state.focused_view = None;
+        }
     }
 
-    pub fn down_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "move_down_and_modify_selection",
-            None as Option<Value>,
-        )
+    /// Retrieve the view that currently has the focus, if any.
+    pub fn focused_view(&self) -> Option<ViewId> {
+        self.state.lock().unwrap().focused_view
     }
 
-    pub fn backspace(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.del(view_id)
+    pub fn goto_line(&mut self, view_id: ViewId, line: u64) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::GotoLine, Some(json!({"line": line})))
     }
 
-    pub fn delete(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "delete_forward", None as Option<Value>)
+    /// Send a `"copy"` or `"cut"` request, and update the kill buffer with
+    /// the text it returns. With the `clipboard` feature, the kill buffer is
+    /// also put on the system clipboard.
+    fn kill(&mut self, view_id: ViewId, method: EditMethod, append: bool) -> RequestResult<Value> {
+        let state = self.state.clone();
+        let result = self.edit_request(view_id, method, None as Option<Value>)
+            .map(move |value| {
+                let mut state = state.lock().unwrap();
+                if !append {
+                    state.kill_buffer.clear();
+                }
+                if let Some(text) = value.as_str() {
+                    state.kill_buffer.push_str(text);
+                }
+                #[cfg(feature = "clipboard")]
+                set_system_clipboard(&state.kill_buffer);
+                value
+            });
+        Box::new(result)
     }
 
-    pub fn del(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "delete_backward", None as Option<Value>)
+    /// Send a `"copy"` request, and return the text that has been copied.
+    /// With the `clipboard` feature, the text is also put on the system
+    /// clipboard.
+    pub fn copy(&mut self, view_id: ViewId) -> RequestResult<Value> {
+        self.kill(view_id, EditMethod::Copy, false)
     }
 
-    pub fn page_up(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "scroll_page_up", None as Option<Value>)
+    pub fn paste(&mut self, view_id: ViewId, buffer: &str) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Paste, Some(json!({"chars": buffer})))
     }
 
-    pub fn page_up_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "page_up_and_modify_selection",
-            None as Option<Value>,
-        )
+    /// Paste a large buffer by pieces of at most `chunk_size` bytes, split
+    /// on character boundaries, to avoid building a single huge message.
+    /// The first piece replaces the selections, and the following ones are
+    /// inserted after it. A buffer that fits in a single piece is sent with
+    /// `paste`.
+    ///
+    /// Since the pieces are sent as `"insert"` commands, with several
+    /// carets the whole buffer is inserted at each caret, unlike `paste`
+    /// which may give a line of the buffer to each caret.
+    pub fn paste_large(&mut self, view_id: ViewId, buffer: &str, chunk_size: usize) -> NotifyResult {
+        let chunks = split_chunks(buffer, chunk_size);
+        if chunks.len() <= 1 {
+            return self.paste(view_id, buffer);
+        }
+        let inserts: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| self.insert(view_id, chunk))
+            .collect();
+        Box::new(future::join_all(inserts).map(|_| ()))
     }
 
-    pub fn page_down(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "scroll_page_down", None as Option<Value>)
+    /// Send a `"cut"` request, and return the text that has been cut. With
+    /// the `clipboard` feature, the text is also put on the system
+    /// clipboard.
+    pub fn cut(&mut self, view_id: ViewId) -> RequestResult<Value> {
+        self.kill(view_id, EditMethod::Cut, false)
     }
 
-    pub fn page_down_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
-            view_id,
-            "page_down_and_modify_selection",
-            None as Option<Value>,
+    /// Like `copy`, but the copied text is appended to the text of the
+    /// previous `copy` or `cut`, like Emacs' "append next kill". The core
+    /// has no such command, so the text is concatenated by the client: the
+    /// future resolves with the whole text, that can be given to `paste`.
+    pub fn copy_append(&mut self, view_id: ViewId) -> RequestResult<String> {
+        let state = self.state.clone();
+        Box::new(
+            self.kill(view_id, EditMethod::Copy, true)
+                .map(move |_| state.lock().unwrap().kill_buffer.clone()),
         )
     }
 
-    pub fn line_start(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "move_to_left_end_of_line", None as Option<Value>)
+    /// Like `cut`, but the text is appended to the text of the previous
+    /// `copy` or `cut`. See `copy_append`.
+    pub fn cut_append(&mut self, view_id: ViewId) -> RequestResult<String> {
+        let state = self.state.clone();
+        Box::new(
+            self.kill(view_id, EditMethod::Cut, true)
+                .map(move |_| state.lock().unwrap().kill_buffer.clone()),
+        )
     }
 
-    pub fn line_start_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(
+    pub fn undo(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Undo, None as Option<Value>)
+    }
+
+    pub fn redo(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Redo, None as Option<Value>)
+    }
+
+    pub fn find(
+        &mut self,
+        view_id: ViewId,
+        search_term: &str,
+        case_sensitive: bool,
+        regex: bool,
+        whole_words: bool,
+    ) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            let view = state.view_mut(view_id);
+            if view.find_origin.is_none() {
+                view.find_origin = Some(view.cache.selections());
+            }
+            view.last_find = Some(FindQuery {
+                chars: search_term.to_string(),
+                case_sensitive,
+                regex,
+                whole_words,
+            });
+        }
+        self.edit_notify(
+            view_id,
+            EditMethod::Find,
+            Some(json!({
+                "chars": search_term,
+                "case_sensitive": case_sensitive,
+                "regex": regex,
+                "whole_words": whole_words}))
+        )
+    }
+
+    /// Return whether the last `find` of a view was case sensitive, or
+    /// `None` if no `find` was issued for this view.
+    pub fn find_case_sensitive(&self, view_id: ViewId) -> Option<bool> {
+        self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .last_find
+            .as_ref()
+            .map(|query| query.case_sensitive)
+    }
+
+    /// Re-issue the last `find` of a view with the given case sensitivity,
+    /// as when toggling case sensitivity in a search box. Nothing is done
+    /// if no `find` was issued for this view.
+    pub fn set_find_case_sensitive(&mut self, view_id: ViewId, case_sensitive: bool) -> NotifyResult {
+        let query = self.state.lock().unwrap().view_mut(view_id).last_find.clone();
+        match query {
+            Some(query) => self.find(
+                view_id,
+                &query.chars,
+                case_sensitive,
+                query.regex,
+                query.whole_words,
+            ),
+            None => Box::new(future::ok(())),
+        }
+    }
+
+    /// Search for the text currently selected. The selection is retrieved
+    /// with a `"copy"` request, and nothing is done if it is empty. Unlike
+    /// `copy`, the kill buffer and the system clipboard are left untouched.
+    pub fn find_selection(&mut self, view_id: ViewId) -> RequestResult<()> {
+        let mut client = self.clone();
+        let copy = self.edit_request(view_id, EditMethod::Copy, None as Option<Value>);
+        let result = copy.and_then(move |selection| {
+            match selection.as_str() {
+                Some(chars) if !chars.is_empty() => {
+                    client.find(view_id, chars, false, false, false)
+                }
+                _ => Box::new(future::ok(())),
+            }
+        });
+        Box::new(result)
+    }
+
+    /// Send a `"selection_for_find"` command, that uses the current
+    /// selection as the query of the search. Unlike `find_selection`, this
+    /// is done by the core, so the kill buffer is not touched.
+    pub fn selection_for_find(
+        &mut self,
+        view_id: ViewId,
+        case_sensitive: bool,
+        whole_words: bool,
+    ) -> NotifyResult {
+        self.edit_notify(
+            view_id,
+            EditMethod::SelectionForFind,
+            Some(json!({
+                "case_sensitive": case_sensitive,
+                "whole_words": whole_words})),
+        )
+    }
+
+    /// Send a `"selection_for_replace"` command, that uses the current
+    /// selection as the replacement text. See `replace`.
+    pub fn selection_for_replace(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::SelectionForReplace, None as Option<Value>)
+    }
+
+    fn find_other(
+        &mut self,
+        view_id: ViewId,
+        command: EditMethod,
+        wrap_around: bool,
+        allow_same: bool,
+        modify_selection: ModifySelection,
+    ) -> NotifyResult {
+        self.edit_notify(
+            view_id,
+            command,
+            Some(json!({
+                "wrap_around": wrap_around,
+                "allow_same": allow_same,
+                "modify_selection": modify_selection}))
+        )
+    }
+
+    pub fn find_next(
+        &mut self,
+        view_id: ViewId,
+        wrap_around: bool,
+        allow_same: bool,
+        modify_selection: ModifySelection,
+    ) -> NotifyResult {
+        self.find_other(
+            view_id,
+            EditMethod::FindNext,
+            wrap_around,
+            allow_same,
+            modify_selection)
+    }
+
+    /// Search for `search_term` and select its first match after the
+    /// carets, wrapping around at the end of the document, as when
+    /// pressing Enter in a search box. This sends a `"find"` followed by
+    /// a `"find_next"`. When the term is not found, the core leaves the
+    /// selections untouched, so the caret does not move. An empty term
+    /// only clears the search.
+    pub fn find_and_select_next(
+        &mut self,
+        view_id: ViewId,
+        search_term: &str,
+        case_sensitive: bool,
+        regex: bool,
+        whole_words: bool,
+    ) -> ClientResult<()> {
+        let mut futures = vec![self.find(view_id, search_term, case_sensitive, regex, whole_words)];
+        if !search_term.is_empty() {
+            futures.push(self.find_next(view_id, true, true, ModifySelection::Set));
+        }
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    pub fn find_prev(
+        &mut self,
+        view_id: ViewId,
+        wrap_around: bool,
+        allow_same: bool,
+        modify_selection: ModifySelection,
+    ) -> NotifyResult {
+        self.find_other(
+            view_id,
+            EditMethod::FindPrevious,
+            wrap_around,
+            allow_same,
+            modify_selection)
+    }
+
+    pub fn find_all(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::FindAll, None as Option<Value>)
+    }
+
+    /// Send a `"find_all"` command, and return a stream of the matches of
+    /// the current search as the core reports them with `"find_status"`
+    /// notifications, by pages of at most `page_size` matches, so that a
+    /// results panel can be filled incrementally. Each notification yields
+    /// the matches it reports for the first time, so the last page of a
+    /// notification may be smaller. The stream goes on reporting the new
+    /// matches, for instance after an edit or a new `find`, until it is
+    /// dropped.
+    pub fn find_all_paged(
+        &mut self,
+        view_id: ViewId,
+        page_size: usize,
+    ) -> Box<Stream<Item = Vec<FindMatch>, Error = ClientError> + Send + Sync> {
+        let page_size = ::std::cmp::max(page_size, 1);
+        let (tx, rx) = mpsc::unbounded();
+        self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .find_status_subscribers
+            .push(tx);
+        let mut reported = HashMap::new();
+        let pages = rx
+            .map(move |status| {
+                let matches = new_find_matches(&mut reported, &status);
+                let pages: Vec<Vec<FindMatch>> =
+                    matches.chunks(page_size).map(|page| page.to_vec()).collect();
+                stream::iter_ok::<_, ()>(pages)
+            })
+            .flatten()
+            .map_err(|_| ClientError::RequestFailed);
+        Box::new(self.find_all(view_id).map(|_| pages).flatten_stream())
+    }
+
+    /// Search for `search_term` and select all its matches, like `find`
+    /// followed by `find_all`, in a way that can be abandoned. The search
+    /// is only sent after `FIND_ALL_DELAY`, and it is dropped if another
+    /// `find_all_cancelable` or a `cancel_find_all` is issued for the view
+    /// meanwhile. This is meant for search boxes that search as the user
+    /// types, so that only the last query reaches the core.
+    pub fn find_all_cancelable(
+        &mut self,
+        view_id: ViewId,
+        search_term: &str,
+        case_sensitive: bool,
+        regex: bool,
+        whole_words: bool,
+    ) -> NotifyResult {
+        let generation = {
+            let mut state = self.state.lock().unwrap();
+            let view = state.view_mut(view_id);
+            view.find_generation += 1;
+            view.find_generation
+        };
+        let mut client = self.clone();
+        let search_term = search_term.to_string();
+        let result = Delay::new(Instant::now() + FIND_ALL_DELAY)
+            .map_err(|_| ClientError::NotifyFailed)
+            .and_then(move |_| -> NotifyResult {
+                let current = client.state.lock().unwrap().view_mut(view_id).find_generation;
+                if current != generation {
+                    return Box::new(future::ok(()));
+                }
+                let futures = vec![
+                    client.find(view_id, &search_term, case_sensitive, regex, whole_words),
+                    client.find_all(view_id),
+                ];
+                Box::new(future::join_all(futures).map(|_| ()))
+            });
+        Box::new(result)
+    }
+
+    /// Abandon the pending `find_all_cancelable` of a view. xi-core has no
+    /// command to cancel a search that was already sent, so the query is
+    /// also cleared with an empty `"find"`, which stops the core from
+    /// searching the rest of the document.
+    pub fn cancel_find_all(&mut self, view_id: ViewId) -> NotifyResult {
+        {
+            let mut state = self.state.lock().unwrap();
+            let view = state.view_mut(view_id);
+            view.find_generation += 1;
+            view.last_find = None;
+        }
+        self.edit_notify(
             view_id,
-            "move_to_left_end_of_line_and_modify_selection",
-            None as Option<Value>,
+            EditMethod::Find,
+            Some(json!({
+                "chars": "",
+                "case_sensitive": false,
+                "regex": false,
+                "whole_words": false})),
+        )
+    }
+
+    /// Cancel the search of a view, as when pressing Escape in a search
+    /// box: the highlights are cleared with an empty `"find"`, and the
+    /// selections are collapsed to a caret where the first caret was
+    /// when the search began, that is, at the first `find` issued since
+    /// the view was opened or since the last `cancel_find`. The caret is
+    /// not moved if that position is not known.
+    pub fn cancel_find(&mut self, view_id: ViewId) -> ClientResult<()> {
+        let origin = self.state.lock().unwrap().view_mut(view_id).find_origin.take();
+        let mut futures = vec![self.cancel_find_all(view_id)];
+        if let Some(region) = origin.as_ref().and_then(|regions| regions.first()) {
+            futures.push(self.restore_selections(view_id, &[SelRegion::caret(region.end)]));
+        }
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    /// Select every match of the regular expression `pattern`, with one
+    /// selection per match. This sends a `"find"` command followed by a
+    /// `"find_all"`. An empty pattern cannot match anything, so in that case
+    /// nothing is sent and the selections are left untouched.
+    pub fn select_all_matches(&mut self, view_id: ViewId, pattern: &str) -> NotifyResult {
+        if pattern.is_empty() {
+            return Box::new(future::ok(()));
+        }
+        let mut client = self.clone();
+        Box::new(
+            self.find(view_id, pattern, true, true, false)
+                .and_then(move |_| client.find_all(view_id)),
         )
     }
 
-    pub fn line_end(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "move_to_right_end_of_line", None as Option<Value>)
+    pub fn highlight_find(&mut self, view_id: ViewId, visible: bool) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::HighlightFind, Some(json!({"visible": visible})))
     }
 
-    pub fn line_end_sel(&mut self, view_id: ViewId) -> ClientResult<()> {
+    /// Set the replacement text used by `replace_next` and `replace_all`.
+    /// The text to replace is the query of the last `find`. If
+    /// `preserve_case` is true, the case of the replaced text is applied to
+    /// the replacement.
+    pub fn replace(
+        &mut self,
+        view_id: ViewId,
+        chars: &str,
+        preserve_case: bool,
+    ) -> NotifyResult {
         self.edit_notify(
             view_id,
-            "move_to_right_end_of_line_and_modify_selection",
-            None as Option<Value>,
+            EditMethod::Replace,
+            Some(json!({"chars": chars, "preserve_case": preserve_case})),
         )
     }
 
-    pub fn select_all(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "select_all", None as Option<Value>)
+    /// Replace the next match of the current `find` query with the text
+    /// given to `replace`. The core then searches for the following match
+    /// as `find_next` does with `ModifySelection::Set`: the selection is
+    /// moved to that match, so that calling `replace_next` repeatedly walks
+    /// through the matches. This is why it does not take a
+    /// `ModifySelection` like `find_next`.
+    pub fn replace_next(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::ReplaceNext, None as Option<Value>)
     }
 
-    pub fn collapse_selections(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "collapse_selections", None as Option<Value>)
+    /// Replace all the matches of the current `find` query with the text
+    /// given to `replace`. The matches are first selected as with
+    /// `find_all`, so afterward there is one caret at the end of each
+    /// replacement.
+    pub fn replace_all(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::ReplaceAll, None as Option<Value>)
     }
 
-    pub fn insert_newline(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "insert_newline", None as Option<Value>)
+    /// Return how the `*_sel` movements modify the selections when they
+    /// are not given a `SelectionMode`. See `set_default_selection_mode`.
+    pub fn default_selection_mode(&self) -> SelectionMode {
+        self.state.lock().unwrap().selection_mode
     }
 
-    pub fn insert_tab(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "insert_tab", None as Option<Value>)
+    /// Set how the `*_sel` movements, such as `left_sel`, modify the
+    /// selections when they are not given a `SelectionMode`. It is
+    /// `SelectionMode::Extend` by default.
+    pub fn set_default_selection_mode(&self, mode: SelectionMode) {
+        self.state.lock().unwrap().selection_mode = mode;
     }
 
-    pub fn f1(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "debug_rewrap", None as Option<Value>)
+    /// Send the `*_sel` movement for the given mode, or for the default
+    /// one if it is `None`. It fails with `ClientError::CannotAddCaret` if
+    /// the movement has no command to add a caret.
+    fn sel_notify(
+        &mut self,
+        view_id: ViewId,
+        extend: EditMethod,
+        add: Option<EditMethod>,
+        mode: Option<SelectionMode>,
+    ) -> NotifyResult {
+        let mode = mode.unwrap_or_else(|| self.default_selection_mode());
+        match sel_movement(extend, add, mode) {
+            Some(method) => self.edit_notify(view_id, method, None as Option<Value>),
+            None => Box::new(future::err(ClientError::CannotAddCaret(extend.as_ref().to_string()))),
+        }
+    }
+
+    pub fn left(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveLeft, None as Option<Value>)
+    }
+
+    /// Move the carets left, modifying the selections as `mode` says, or
+    /// as set with `set_default_selection_mode` if it is `None`. Adding a
+    /// caret fails with `ClientError::CannotAddCaret`.
+    pub fn left_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(view_id, EditMethod::MoveLeftAndModifySelection, None, mode)
+    }
+
+    pub fn right(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveRight, None as Option<Value>)
+    }
+
+    /// Like `right`, modifying the selections. See `left_sel`.
+    pub fn right_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(view_id, EditMethod::MoveRightAndModifySelection, None, mode)
+    }
+
+    /// Move the carets by `delta` characters, to the right if `delta` is
+    /// positive and to the left otherwise, wrapping to the next or previous
+    /// line. If `extend` is true, the selections are extended. The core has
+    /// no command to move by several columns, so the new selections are
+    /// computed from the line cache and set at once. Carets on lines the
+    /// core did not send are not moved.
+    pub fn move_horizontal(&mut self, view_id: ViewId, delta: i64, extend: bool) -> NotifyResult {
+        let regions = self.selections(view_id);
+        let mut moved: Vec<SelRegion> = {
+            let mut state = self.state.lock().unwrap();
+            let cache = &state.view_mut(view_id).cache;
+            let text = |line: u64| {
+                cache
+                    .line(line)
+                    .map(|l| l.text.trim_end_matches(&['\n', '\r'][..]))
+            };
+            regions
+                .iter()
+                .map(|region| {
+                    let end = shift_column(text, region.end, delta);
+                    if extend {
+                        SelRegion::new(region.start, end)
+                    } else {
+                        SelRegion::caret(end)
+                    }
+                })
+                .collect()
+        };
+        moved.dedup();
+        if delta == 0 || moved.is_empty() {
+            return Box::new(future::ok(()));
+        }
+        self.restore_selections(view_id, &moved)
+    }
+
+    pub fn up(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveUp, None as Option<Value>)
+    }
+
+    /// Like `up`, modifying the selections. See `left_sel`.
+    pub fn up_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(
+            view_id,
+            EditMethod::MoveUpAndModifySelection,
+            Some(EditMethod::AddSelectionAbove),
+            mode,
+        )
+    }
+
+    pub fn down(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveDown, None as Option<Value>)
+    }
+
+    /// Like `down`, modifying the selections. See `left_sel`.
+    pub fn down_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(
+            view_id,
+            EditMethod::MoveDownAndModifySelection,
+            Some(EditMethod::AddSelectionBelow),
+            mode,
+        )
+    }
+
+    pub fn backspace(&mut self, view_id: ViewId) -> NotifyResult {
+        self.del(view_id)
+    }
+
+    pub fn delete(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::DeleteForward, None as Option<Value>)
+    }
+
+    pub fn del(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::DeleteBackward, None as Option<Value>)
+    }
+
+    pub fn page_up(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::ScrollPageUp, None as Option<Value>)
+    }
+
+    /// Like `page_up`, modifying the selections. See `left_sel`.
+    pub fn page_up_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(view_id, EditMethod::PageUpAndModifySelection, None, mode)
+    }
+
+    pub fn page_down(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::ScrollPageDown, None as Option<Value>)
+    }
+
+    /// Like `page_down`, modifying the selections. See `left_sel`.
+    pub fn page_down_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(view_id, EditMethod::PageDownAndModifySelection, None, mode)
+    }
+
+    pub fn line_start(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveToLeftEndOfLine, None as Option<Value>)
+    }
+
+    /// Like `line_start`, modifying the selections. See `left_sel`.
+    pub fn line_start_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(view_id, EditMethod::MoveToLeftEndOfLineAndModifySelection, None, mode)
+    }
+
+    pub fn line_end(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveToRightEndOfLine, None as Option<Value>)
+    }
+
+    /// Like `line_end`, modifying the selections. See `left_sel`.
+    pub fn line_end_sel(&mut self, view_id: ViewId, mode: Option<SelectionMode>) -> NotifyResult {
+        self.sel_notify(view_id, EditMethod::MoveToRightEndOfLineAndModifySelection, None, mode)
+    }
+
+    pub fn select_all(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::SelectAll, None as Option<Value>)
+    }
+
+    pub fn collapse_selections(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::CollapseSelections, None as Option<Value>)
+    }
+
+    /// Move the carets of a view to the start of the document.
+    pub fn document_start(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::MoveToBeginningOfDocument, None as Option<Value>)
+    }
+
+    /// Put a view back in a known state: the selections are collapsed and
+    /// the remaining caret is moved to the start of the document, so that
+    /// a single caret is left at `(0, 0)`, whatever the selections were.
+    pub fn reset_caret(&mut self, view_id: ViewId) -> ClientResult<()> {
+        let futures = vec![
+            self.collapse_selections(view_id),
+            self.document_start(view_id),
+        ];
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    /// Comment out the lines that contain the selections with line
+    /// comments, or uncomment them if they are all commented out.
+    pub fn toggle_comment(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::ToggleComment, None as Option<Value>)
+    }
+
+    /// Wrap each selection in a block comment, such as `/* */`, or remove
+    /// the block comment that wraps it. xi-core only has a command for
+    /// line comments, so this is done by the client: the delimiters are
+    /// chosen from the language of the view (see `language`), and each
+    /// selection is replaced with `replace_range`, which leaves a caret at
+    /// its end. Nothing is done for the carets, for the selections on lines
+    /// that are not in the cache, or if the language has no block comments.
+    pub fn toggle_block_comment(&mut self, view_id: ViewId) -> ClientResult<()> {
+        let (open, close) = match self.language(view_id).and_then(|l| block_comment_delimiters(&l)) {
+            Some(delimiters) => delimiters,
+            None => return Box::new(future::ok(())),
+        };
+        let mut client = self.clone();
+        let result = self.selection_snapshot(view_id).and_then(move |snapshot| {
+            // Replacing the selections from the last one keeps the positions
+            // of the others valid.
+            let replacements: Vec<_> = snapshot
+                .into_iter()
+                .rev()
+                .filter(|(region, _)| !region.is_caret())
+                .map(|(region, text)| {
+                    let (start, end) = if region.start <= region.end {
+                        (region.start, region.end)
+                    } else {
+                        (region.end, region.start)
+                    };
+                    client.replace_range(view_id, start, end, &toggle_block_comment_text(&text, open, close))
+                })
+                .collect();
+            future::join_all(replacements).map(|_| ())
+        });
+        Box::new(result)
+    }
+
+    /// Indent the lines that contain the selections by one level.
+    pub fn indent(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Indent, None as Option<Value>)
+    }
+
+    /// Remove one level of indentation from the lines that contain the
+    /// selections.
+    pub fn outdent(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Outdent, None as Option<Value>)
+    }
+
+    /// Indent the lines that contain the selections by `levels` levels, or
+    /// outdent them if `levels` is negative. xi-core has no command taking
+    /// a number of levels, so one `"indent"` or `"outdent"` is sent per
+    /// level, each of them being a separate step in the undo history.
+    pub fn indent_by(&mut self, view_id: ViewId, levels: i32) -> NotifyResult {
+        let method = if levels > 0 { EditMethod::Indent } else { EditMethod::Outdent };
+        let futures = (0..levels.unsigned_abs())
+            .map(|_| self.edit_notify(view_id, method, None as Option<Value>))
+            .collect::<Vec<_>>();
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    pub fn insert_newline(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::InsertNewline, None as Option<Value>)
+    }
+
+    pub fn insert_tab(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::InsertTab, None as Option<Value>)
+    }
+
+    pub fn f1(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::DebugRewrap, None as Option<Value>)
+    }
+
+    pub fn f2(&mut self, view_id: ViewId) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::DebugTestFgSpans, None as Option<Value>)
+    }
+
+    pub fn char(&mut self, view_id: ViewId, ch: char) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Insert, Some(json!({ "chars": ch })))
+    }
+
+    /// Insert a grapheme cluster, like an emoji with a skin tone modifier
+    /// or a letter followed by combining marks, that cannot be represented
+    /// by a single `char`. It is sent as a single `"insert"`, so that the
+    /// core never sees a partial cluster, for instance when committing the
+    /// text composed by an input method.
+    pub fn insert_grapheme(&mut self, view_id: ViewId, grapheme: &str) -> NotifyResult {
+        self.insert(view_id, grapheme)
+    }
+
+    fn insert(&mut self, view_id: ViewId, chars: &str) -> NotifyResult {
+        self.edit_notify(view_id, EditMethod::Insert, Some(json!({ "chars": chars })))
+    }
+
+    /// Insert `text` at the given position, and put the carets back where
+    /// they were. The carets that were after the insertion point are moved
+    /// so that they stay at the same place relative to the text around
+    /// them.
+    ///
+    /// Only the insertion is recorded in the undo history: undoing it
+    /// removes the text but does not move the carets.
+    pub fn insert_at(
+        &mut self,
+        view_id: ViewId,
+        line: u64,
+        column: u64,
+        text: &str,
+    ) -> NotifyResult {
+        let at = Position::new(line, column);
+        let regions: Vec<SelRegion> = self.selections(view_id)
+            .into_iter()
+            .map(|region| {
+                SelRegion::new(
+                    shift_position(region.start, &at, text),
+                    shift_position(region.end, &at, text),
+                )
+            })
+            .collect();
+        let mut futures = vec![
+            self.gesture(view_id, line, column, "point_select"),
+            self.insert(view_id, text),
+        ];
+        if !regions.is_empty() {
+            futures.push(self.restore_selections(view_id, &regions));
+        }
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    /// Replace the text between the `start` and `end` positions with
+    /// `text`. This is the primitive text edits
+    /// from formatters or language servers map onto.
+    ///
+    /// The range is selected with `"gesture"` notifications, which are not
+    /// recorded in the undo history, and the selection is then replaced by
+    /// a single `"insert"`, so undoing the replacement is a single undo
+    /// group. The selections of the view are replaced by a caret at the end
+    /// of the inserted text.
+    pub fn replace_range<S: Into<Position>, E: Into<Position>>(
+        &mut self,
+        view_id: ViewId,
+        start: S,
+        end: E,
+        text: &str,
+    ) -> NotifyResult {
+        let (start, end) = (start.into(), end.into());
+        let futures = vec![
+            self.gesture(view_id, start.line, start.col, "point_select"),
+            self.gesture(view_id, end.line, end.col, "range_select"),
+            self.insert(view_id, text),
+        ];
+        Box::new(future::join_all(futures).map(|_| ()))
     }
 
-    pub fn f2(&mut self, view_id: ViewId) -> ClientResult<()> {
-        self.edit_notify(view_id, "debug_test_fg_spans", None as Option<Value>)
+    /// Move the lines that contain the selections one line up, like
+    /// Alt+Up in many editors. The core has no such command, so the lines
+    /// are swapped by the client, which requires the lines involved to be
+    /// in the line cache. Nothing is done if the lines are already at the
+    /// top of the document, or if they are not in the cache. The selections
+    /// are moved along with the lines.
+    pub fn move_lines_up(&mut self, view_id: ViewId) -> NotifyResult {
+        self.move_lines(view_id, true)
     }
 
-    pub fn char(&mut self, view_id: ViewId, ch: char) -> ClientResult<()> {
-        self.edit_notify(view_id, "insert", Some(json!({ "chars": ch })))
+    /// Move the lines that contain the selections one line down. See
+    /// `move_lines_up`.
+    pub fn move_lines_down(&mut self, view_id: ViewId) -> NotifyResult {
+        self.move_lines(view_id, false)
+    }
+
+    fn move_lines(&mut self, view_id: ViewId, up: bool) -> NotifyResult {
+        let regions = self.selections(view_id);
+        let (first, last) = match selected_lines(&regions) {
+            Some((first, last)) if up && first > 0 => (first - 1, last),
+            Some((first, last)) if !up => (first, last + 1),
+            _ => return Box::new(future::ok(())),
+        };
+        let lines: Vec<String> = {
+            let mut state = self.state.lock().unwrap();
+            let cache = &state.view_mut(view_id).cache;
+            if first < cache.before() {
+                return Box::new(future::ok(()));
+            }
+            cache.lines()
+                .iter()
+                .skip((first - cache.before()) as usize)
+                .take((last - first + 1) as usize)
+                .map(|line| line.text.trim_end_matches('\n').to_string())
+                .collect()
+        };
+        if lines.len() as u64 != last - first + 1 {
+            // The line after the selections is not in the cache, or does
+            // not exist.
+            return Box::new(future::ok(()));
+        }
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+        let last_column = lines[lines.len() - 1].len() as u64;
+        let regions: Vec<SelRegion> = regions
+            .into_iter()
+            .map(|region| {
+                let shift = |Position { line, col: column }| {
+                    if up {
+                        Position::new(line - 1, column)
+                    } else {
+                        Position::new(line + 1, column)
+                    }
+                };
+                SelRegion::new(shift(region.start), shift(region.end))
+            })
+            .collect();
+        let futures = vec![
+            self.gesture(view_id, first, 0, "point_select"),
+            self.gesture(view_id, last, last_column, "range_select"),
+            self.insert(view_id, &swap_lines(&lines, up)),
+            self.restore_selections(view_id, &regions),
+        ];
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    /// Delete the lines that contain the selections, along with their
+    /// newline. The core has no such command, so the lines are selected
+    /// with `"gesture"` notifications, which are not recorded in the undo
+    /// history, and removed with a single `"delete_backward"`: deleting
+    /// the lines is a single undo group. On the last line of the document,
+    /// which has no trailing newline, the newline before the lines is
+    /// removed instead, so that no empty line is left behind. With several
+    /// selections, all the lines from the first selection to the last one
+    /// are deleted. Nothing is done if one of the lines involved is not in
+    /// the cache.
+    pub fn delete_line(&mut self, view_id: ViewId) -> ClientResult<()> {
+        let (first, last) = match selected_lines(&self.selections(view_id)) {
+            Some(lines) => lines,
+            None => return Box::new(future::ok(())),
+        };
+        let range = {
+            let mut state = self.state.lock().unwrap();
+            let cache = &state.view_mut(view_id).cache;
+            let len = |line_nb| {
+                cache
+                    .line(line_nb)
+                    .map(|line| line.text.trim_end_matches('\n').len() as u64)
+            };
+            match len(last) {
+                Some(last_len) => {
+                    let previous_len = if first > 0 { len(first - 1) } else { None };
+                    line_deletion_range(first, last, cache.nb_lines(), previous_len, last_len)
+                }
+                None => None,
+            }
+        };
+        let (start, end) = match range {
+            Some(range) => range,
+            None => return Box::new(future::ok(())),
+        };
+        let futures = vec![
+            self.gesture(view_id, start.line, start.col, "point_select"),
+            self.gesture(view_id, end.line, end.col, "range_select"),
+            self.del(view_id),
+        ];
+        Box::new(future::join_all(futures).map(|_| ()))
+    }
+
+    /// Move the carets to the start of the previous subword, stopping at
+    /// case changes and underscores as well as at whitespace, which is
+    /// convenient to move through identifiers such as `fooBarBaz`. The core
+    /// has no such command, so the positions are computed by the client
+    /// from the line cache, and the carets are placed with `"gesture"`
+    /// notifications. The carets on lines that are not in the cache are not
+    /// moved, and the selections are collapsed.
+    pub fn subword_left(&mut self, view_id: ViewId) -> NotifyResult {
+        self.move_subword(view_id, false)
+    }
+
+    /// Move the carets to the end of the next subword. See `subword_left`.
+    pub fn subword_right(&mut self, view_id: ViewId) -> NotifyResult {
+        self.move_subword(view_id, true)
+    }
+
+    fn move_subword(&mut self, view_id: ViewId, forward: bool) -> NotifyResult {
+        let regions = self.selections(view_id);
+        let mut carets: Vec<SelRegion> = {
+            let mut state = self.state.lock().unwrap();
+            let cache = &state.view_mut(view_id).cache;
+            let text = |line: u64| {
+                cache
+                    .line(line)
+                    .map(|l| l.text.trim_end_matches(&['\n', '\r'][..]))
+            };
+            regions
+                .iter()
+                .map(|region| {
+                    let Position { line, col } = region.end;
+                    let position = match text(line) {
+                        Some(current) if forward && col as usize >= current.len() => {
+                            match text(line + 1) {
+                                Some(_) => Position::new(line + 1, 0),
+                                None => Position::new(line, col),
+                            }
+                        }
+                        Some(current) if forward => {
+                            Position::new(line, next_subword_boundary(current, col as usize) as u64)
+                        }
+                        Some(_) if col == 0 => match line.checked_sub(1).and_then(&text) {
+                            Some(previous) => Position::new(line - 1, previous.len() as u64),
+                            None => Position::new(line, col),
+                        },
+                        Some(current) => {
+                            let col = ::std::cmp::min(col as usize, current.len());
+                            Position::new(line, prev_subword_boundary(current, col) as u64)
+                        }
+                        None => Position::new(line, col),
+                    };
+                    SelRegion::caret(position)
+                })
+                .collect()
+        };
+        carets.dedup();
+        if carets.is_empty() {
+            return Box::new(future::ok(()));
+        }
+        self.restore_selections(view_id, &carets)
     }
 
     // FIXME: handle modifier and click count
-    pub fn click(&mut self, view_id: ViewId, line: u64, column: u64) -> ClientResult<()> {
-        self.edit_notify(view_id, "click", Some(json!([line, column, 0, 1])))
+    pub fn click<P: Into<Position>>(&mut self, view_id: ViewId, position: P) -> NotifyResult {
+        let Position { line, col } = position.into();
+        self.edit_notify(view_id, EditMethod::Click, Some(json!([line, col, 0, 1])))
     }
 
     pub fn click_point_select(
@@ -339,11 +2060,12 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
+        self.set_selection_granularity(view_id, SelectionGranularity::Char);
         let ty = "point_select";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
@@ -353,11 +2075,11 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
         let ty = "toggle_sel";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
@@ -367,11 +2089,11 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
         let ty = "range_select";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
@@ -381,11 +2103,12 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
+        self.set_selection_granularity(view_id, SelectionGranularity::Line);
         let ty = "range_select";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
@@ -395,11 +2118,12 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
+        self.set_selection_granularity(view_id, SelectionGranularity::Word);
         let ty = "word_select";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
@@ -409,11 +2133,11 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
         let ty = "multi_line_select";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
@@ -423,77 +2147,722 @@ impl Client {
         view_id: ViewId,
         line: u64,
         column: u64,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
         let ty = "multi_word_select";
         self.edit_notify(
             view_id,
-            "gesture",
+            EditMethod::Gesture,
             Some(json!({"line": line, "col": column, "ty": ty,})),
         )
     }
 
-    pub fn drag(&mut self, view_id: ViewId, line: u64, column: u64) -> ClientResult<()> {
-        self.edit_notify(view_id, "drag", Some(json!([line, column, 0])))
+    fn gesture(&mut self, view_id: ViewId, line: u64, column: u64, ty: &str) -> NotifyResult {
+        self.edit_notify(
+            view_id,
+            EditMethod::Gesture,
+            Some(json!({"line": line, "col": column, "ty": ty,})),
+        )
     }
 
-    /// send a `"new_view"` request to the core.
+    /// Replace the selections of a view by the given regions, using
+    /// `"gesture"` notifications. Regions must be in document order.
+    fn restore_selections(&mut self, view_id: ViewId, regions: &[SelRegion]) -> NotifyResult {
+        let mut gestures = Vec::new();
+        for (idx, region) in regions.iter().enumerate() {
+            let ty = if idx == 0 { "point_select" } else { "toggle_sel" };
+            gestures.push(self.gesture(view_id, region.start.line, region.start.col, ty));
+            if !region.is_caret() {
+                gestures.push(self.gesture(view_id, region.end.line, region.end.col, "range_select"));
+            }
+        }
+        Box::new(future::join_all(gestures).map(|_| ()))
+    }
+
+    /// Replace the selections of a view by the given regions, for instance
+    /// to restore a saved multi-cursor state or to place the carets on the
+    /// tab stops of a snippet. xi-core has no command setting several
+    /// selections at once, so they are set with one `"gesture"`
+    /// notification per caret, and one more per non-empty region. The
+    /// regions are sorted in document order first, and they should not
+    /// overlap. Nothing is done if `regions` is empty.
+    pub fn set_selections(&mut self, view_id: ViewId, mut regions: Vec<SelRegion>) -> ClientResult<()> {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        regions.sort_by(|a, b| {
+            let a = ::std::cmp::min(&a.start, &a.end);
+            let b = ::std::cmp::min(&b.start, &b.end);
+            a.cmp(b)
+        });
+        self.restore_selections(view_id, &regions)
+    }
+
+    /// Return the selections of a view, as known from the last updates
+    /// received from the core. Only the selections of the lines the core
+    /// sent are known.
+    pub fn selections(&self, view_id: ViewId) -> Vec<SelRegion> {
+        self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .cache
+            .selections()
+    }
+
+    /// Return the selections of a view along with the text they cover, as
+    /// known from the last updates received from the core. Both are read
+    /// from the line cache at once, so they are consistent with each other.
+    /// Only the selections of the lines the core sent are known.
+    pub fn selection_snapshot(&mut self, view_id: ViewId) -> ClientResult<Vec<(SelRegion, String)>> {
+        let mut state = self.state.lock().unwrap();
+        let cache = &state.view_mut(view_id).cache;
+        let snapshot = cache
+            .selections()
+            .into_iter()
+            .filter_map(|region| {
+                let text = cache.text(&region.start, &region.end)?;
+                Some((region, text))
+            })
+            .collect();
+        Box::new(future::ok(snapshot))
+    }
+
+    /// Return the position of the caret once the core has sent the next
+    /// update of the view, typically the one following a movement such as
+    /// `left` or `down`. The update is awaited from the moment this method
+    /// is called, so it should be called right after the movement. With
+    /// several carets, the first one in the document is returned. The
+    /// future resolves with `None` if the caret is on a line the core did
+    /// not send.
+    pub fn caret_position(&mut self, view_id: ViewId) -> ClientResult<Option<Position>> {
+        let state = self.state.clone();
+        let result = self.next_update(view_id).map(move |_| {
+            state
+                .lock()
+                .unwrap()
+                .view_mut(view_id)
+                .cache
+                .selections()
+                .first()
+                .map(|region| region.end)
+        });
+        Box::new(result)
+    }
+
+    /// Run `f`, and restore the current selections of the view once the
+    /// future it returns has completed. This is meant for programmatic
+    /// edits, like transforming the selected text, that should not change
+    /// the user's selections.
+    pub fn with_preserved_selection<F>(&mut self, view_id: ViewId, f: F) -> ClientResult<()>
+    where
+        F: FnOnce(&mut Client) -> ClientResult<()>,
+    {
+        let regions = self.selections(view_id);
+        let mut client = self.clone();
+        let result = f(self).and_then(move |_| {
+            if regions.is_empty() {
+                Box::new(future::ok(()))
+            } else {
+                client.restore_selections(view_id, &regions)
+            }
+        });
+        Box::new(result)
+    }
+
+    /// Send a `"gesture"` command with the modifier keys that were held,
+    /// unlike the `click_*` methods that only send the type of the gesture.
+    /// ```ignore
+    /// {"method":"edit","params":{"method":"gesture","params":{"line":1,
+    /// "col":4,"ty":"word_select","modifiers":2},"view_id":"view-id-1"}}
+    /// ```
+    pub fn gesture_ex(
+        &mut self,
+        view_id: ViewId,
+        line: u64,
+        column: u64,
+        ty: GestureType,
+        modifiers: ClickModifiers,
+    ) -> NotifyResult {
+        self.edit_notify(
+            view_id,
+            EditMethod::Gesture,
+            Some(json!({"line": line, "col": column, "ty": ty, "modifiers": modifiers})),
+        )
+    }
+
+    /// Extend the selection of a view to the given position. The
+    /// selection is extended by the granularity of the view (see
+    /// `set_selection_granularity`), so that a drag following a
+    /// `click_word_select` extends the selection word by word.
+    pub fn drag<P: Into<Position>>(&mut self, view_id: ViewId, position: P) -> NotifyResult {
+        let Position { line, col } = position.into();
+        match self.selection_granularity(view_id) {
+            SelectionGranularity::Char => {
+                self.edit_notify(view_id, EditMethod::Drag, Some(json!([line, col, 0])))
+            }
+            granularity => self.edit_notify(
+                view_id,
+                EditMethod::Gesture,
+                Some(json!({
+                    "line": line,
+                    "col": col,
+                    "ty": {"select_extend": {"granularity": granularity}},
+                })),
+            ),
+        }
+    }
+
+    /// Return the granularity applied to the `drag` gestures of a view.
+    pub fn selection_granularity(&self, view_id: ViewId) -> SelectionGranularity {
+        self.state.lock().unwrap().view_mut(view_id).granularity
+    }
+
+    /// Set the granularity applied to the subsequent `drag` gestures of a
+    /// view. It is also set by `click_point_select` (`Char`),
+    /// `click_word_select` (`Word`) and `click_line_select` (`Line`).
+    pub fn set_selection_granularity(&mut self, view_id: ViewId, granularity: SelectionGranularity) {
+        self.state.lock().unwrap().view_mut(view_id).granularity = granularity;
+    }
+
+    /// send a `"new_view"` request to the core.
     /// ```ignore
     /// {"id":1,"method":"new_view","params":{"file_path":"foo/test.txt"}}
     /// ```
-    pub fn new_view(&mut self, file_path: Option<String>) -> ClientResult<ViewId> {
-        let params = if let Some(file_path) = file_path {
+    pub fn new_view(&mut self, file_path: Option<String>) -> RequestResult<ViewId> {
+        let params = if let Some(ref file_path) = file_path {
             json!({ "file_path": file_path })
         } else {
             json!({})
         };
+        let state = self.state.clone();
         let result = self.request("new_view", params)
-            .and_then(|result| from_value::<ViewId>(result).map_err(From::from));
+            .and_then(|result| from_value::<ViewId>(result).map_err(From::from))
+            .map(move |view_id| {
+                let mut state = state.lock().unwrap();
+                let _ = state.open_views.insert(view_id);
+                state.view_mut(view_id).file_path = file_path;
+                view_id
+            });
         Box::new(result)
     }
 
+    /// Open a view on a file with the caret at the given position, for
+    /// instance to open a "find in files" result. The future resolves with
+    /// the new view once the caret has been placed. The core then sends a
+    /// `"scroll_to"` notification for the caret; frontends that prefer the
+    /// line to be centered can call `center_line` afterward, since the
+    /// height of the viewport is only known to them.
+    pub fn new_view_at(&mut self, file_path: &str, line: u64, column: u64) -> RequestResult<ViewId> {
+        let mut client = self.clone();
+        let result = self.new_view(Some(file_path.to_string())).and_then(move |view_id| {
+            client
+                .gesture(view_id, line, column, "point_select")
+                .map(move |_| view_id)
+        });
+        Box::new(result)
+    }
+
+    /// Open a second view on the file of the given view, for instance for a
+    /// split pane. xi-core has no way to open several views on the same
+    /// buffer, so the file is opened again in a new buffer: the edits made
+    /// in one view are not reflected in the other, and the unsaved changes
+    /// of the original view are not in the new one. A view that has no file
+    /// is duplicated as an empty view.
+    pub fn duplicate_view(&mut self, view_id: ViewId) -> RequestResult<ViewId> {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let file_path = self.state.lock().unwrap().view_mut(view_id).file_path.clone();
+        self.new_view(file_path)
+    }
+
     /// send a `"close_view"` notifycation to the core.
-    pub fn close_view(&mut self, view_id: ViewId) -> ClientResult<()> {
+    pub fn close_view(&mut self, view_id: ViewId) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            let _ = state.views.remove(&view_id);
+            let _ = state.open_views.remove(&view_id);
+            if state.focused_view == Some(view_id) {
+                state.focused_view = None;
+            }
+        }
         self.notify("close_view", json!({ "view_id": view_id }))
     }
 
-    pub fn save(&mut self, view_id: ViewId, file_path: &str) -> ClientResult<()> {
+    pub fn save(&mut self, view_id: ViewId, file_path: &str) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        self.state.lock().unwrap().view_mut(view_id).file_path = Some(file_path.to_string());
         let params = json!({"view_id": view_id, "file_path": file_path});
         Box::new(self.notify("save", params).and_then(|_| Ok(())))
     }
 
-    pub fn set_theme(&mut self, theme: &str) -> ClientResult<()> {
+    /// Return whether a view has no unsaved changes, as reported by the last
+    /// update of the view. If no update has been received yet, the future
+    /// waits for the next one.
+    pub fn is_pristine(&mut self, view_id: ViewId) -> ClientResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let view = state.view_mut(view_id);
+        if let Some(pristine) = view.pristine {
+            return Box::new(future::ok(pristine));
+        }
+        let (tx, rx) = oneshot::channel();
+        view.update_waiters.push(tx);
+        Box::new(
+            rx.map(|update: Update| update.pristine)
+                .map_err(|_| ClientError::RequestFailed),
+        )
+    }
+
+    /// Return a stream that yields whether a view has no unsaved changes
+    /// every time this changes: when the first edit makes the view dirty,
+    /// and when a save or an undo makes it pristine again. If an update of
+    /// the view has already been received, the stream first yields the
+    /// current state, so that a modified indicator can be initialized from
+    /// it.
+    pub fn pristine_stream(&self, view_id: ViewId) -> Box<Stream<Item = bool, Error = ()> + Send + Sync> {
+        let (tx, rx) = mpsc::unbounded();
+        let mut state = self.state.lock().unwrap();
+        let view = state.view_mut(view_id);
+        if let Some(pristine) = view.pristine {
+            let _ = tx.unbounded_send(pristine);
+        }
+        view.pristine_subscribers.push(tx);
+        Box::new(rx)
+    }
+
+    /// Save several views, each to the given file. All the saves are
+    /// issued even if some of them fail, and the future resolves with the
+    /// result of each save, in the same order as `views`.
+    ///
+    /// The core does not answer `"save"`: a save is confirmed by the update
+    /// that marks the view as pristine, and fails with
+    /// `ClientError::SaveNotConfirmed` if it does not arrive within
+    /// `timeout`, for instance because the file could not be written. A
+    /// view that is already pristine is considered saved as soon as the
+    /// notification is sent. The timeouts need a tokio runtime.
+    pub fn save_all(
+        &mut self,
+        views: &[(ViewId, &str)],
+        timeout: Duration,
+    ) -> ClientResult<Vec<Result<(), ClientError>>> {
+        let saves: Vec<_> = views
+            .iter()
+            .map(|&(view_id, file_path)| {
+                let pristine = self
+                    .pristine_stream(view_id)
+                    .skip_while(|pristine| Ok(!*pristine))
+                    .into_future()
+                    .map_err(|_| ClientError::RequestFailed);
+                let confirmed = Timeout::new(pristine, timeout).then(move |result| match result {
+                    Ok((Some(_), _)) => Ok(()),
+                    Ok((None, _)) => Err(ClientError::RequestFailed),
+                    Err(e) => Err(e.into_inner().unwrap_or(ClientError::SaveNotConfirmed(view_id))),
+                });
+                self.save(view_id, file_path).and_then(|_| confirmed).then(Ok)
+            })
+            .collect();
+        Box::new(future::join_all(saves))
+    }
+
+    /// Return the languages supported by the core. The future resolves
+    /// immediately if the core already sent an `"available_languages"`
+    /// notification, and waits for it otherwise.
+    pub fn languages(&mut self) -> ClientResult<Vec<String>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(ref languages) = state.languages {
+            return Box::new(future::ok(languages.clone()));
+        }
+        let (tx, rx) = oneshot::channel();
+        state.languages_waiters.push(tx);
+        Box::new(rx.map_err(|_| ClientError::RequestFailed))
+    }
+
+    /// Return a stream of the changes of the state of the connection with
+    /// the core, so that the frontend can show whether it is connected
+    /// without waiting for a message to fail. It first yields the current
+    /// state, and then `ConnectionEvent::Disconnected` once the connection
+    /// is closed.
+    pub fn connection_events(&self) -> Box<Stream<Item = ConnectionEvent, Error = ()> + Send + Sync> {
+        let (tx, rx) = mpsc::unbounded();
+        let mut state = self.state.lock().unwrap();
+        if let Some(ref event) = state.connection {
+            let _ = tx.unbounded_send(event.clone());
+        }
+        state.connection_subscribers.push(tx);
+        Box::new(rx)
+    }
+
+    /// Return a stream of the themes supported by the core. It first yields
+    /// the themes the core already announced, if any, and then the new list
+    /// every time the core sends an `"available_themes"` notification, so
+    /// that a theme picker stays up to date.
+    pub fn theme_list_stream(&self) -> Box<Stream<Item = Vec<String>, Error = ()> + Send + Sync> {
+        let (tx, rx) = mpsc::unbounded();
+        let mut state = self.state.lock().unwrap();
+        if let Some(ref themes) = state.themes {
+            let _ = tx.unbounded_send(themes.clone());
+        }
+        state.themes_subscribers.push(tx);
+        Box::new(rx)
+    }
+
+    /// Register a function answering the requests of the given method that
+    /// the core forwards on behalf of plugins, for instance to ask for the
+    /// current selection. The function receives the parameters of the
+    /// request, and returns the result or the error sent back. It replaces
+    /// any function previously registered for this method. The requests
+    /// that have no registered function are answered with an error.
+    pub fn register_request_handler<F>(&self, method: &str, handler: F)
+    where
+        F: FnMut(Value) -> Result<Value, Value> + Send + 'static,
+    {
+        let _ = self
+            .state
+            .lock()
+            .unwrap()
+            .request_handlers
+            .insert(method.to_string(), RequestHandler(Box::new(handler)));
+    }
+
+    /// Return a stream of the notifications the core sends on behalf of the
+    /// given plugin, as `(method, params)` pairs. A notification is
+    /// attributed to a plugin when its parameters have a `"plugin"` or
+    /// `"source"` field with the plugin's name. The notifications are also
+    /// dispatched to the `Frontend` as usual.
+    pub fn plugin_notifications(
+        &self,
+        plugin: &str,
+    ) -> Box<Stream<Item = (String, Value), Error = ()> + Send + Sync> {
+        let (tx, rx) = mpsc::unbounded();
+        self.state
+            .lock()
+            .unwrap()
+            .plugin_subscribers
+            .push((plugin.to_string(), tx));
+        Box::new(rx)
+    }
+
+    /// Return the language of a view, as reported by the last
+    /// `"language_changed"` notification for this view.
+    pub fn language(&self, view_id: ViewId) -> Option<String> {
+        self.state.lock().unwrap().view_mut(view_id).language.clone()
+    }
+
+    /// Send a `"set_language"` notification, to change the language used
+    /// for the syntax highlighting of a view.
+    pub fn set_language(&mut self, view_id: ViewId, language_id: &str) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let params = json!({"view_id": view_id, "language_id": language_id});
+        self.notify("set_language", params)
+    }
+
+    /// Send a `"set_language"` notification, and resolve once the core has
+    /// sent the corresponding `"language_changed"` notification, so that the
+    /// frontend can show that the view is being highlighted again. The
+    /// future resolves right after the notification is sent if the view
+    /// already has this language, since the core does not confirm it then.
+    pub fn set_language_confirmed(&mut self, view_id: ViewId, language_id: &str) -> ClientResult<()> {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            let view = state.view_mut(view_id);
+            if view.language.as_deref() == Some(language_id) {
+                let _ = tx.send(());
+            } else {
+                view.language_waiters.push((language_id.to_string(), tx));
+            }
+        }
+        let rx = rx.map_err(|_| ClientError::RequestFailed);
+        Box::new(self.set_language(view_id, language_id).and_then(|_| rx))
+    }
+
+    pub fn set_theme(&mut self, theme: &str) -> NotifyResult {
         let params = json!({ "theme_name": theme });
         Box::new(self.notify("set_theme", params).and_then(|_| Ok(())))
     }
 
-    pub fn client_started(&mut self, config_dir: Option<&str>, client_extra_dir: Option<&str>) -> ClientResult<()> {
-        let mut params = Map::new();
-        if let Some(path) = config_dir {
-            let _ = params.insert("config_dir".into(), json!(path));
+    /// Send a `"set_theme"` notification, and resolve with the colors of the
+    /// theme once the core has sent the corresponding `"theme_changed"`
+    /// notification, so that the frontend can repaint with the new colors
+    /// all at once. The core ignores the themes it does not know, so it
+    /// fails with `ClientError::UnknownTheme` without sending anything if
+    /// the theme is not among the ones announced by the last
+    /// `"available_themes"` notification, or if none has been received.
+    pub fn set_theme_confirmed(&mut self, theme: &str) -> ClientResult<ThemeChanged> {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            let known = state.themes.iter().flat_map(|themes| themes.iter()).any(|t| t == theme);
+            if !known {
+                return Box::new(future::err(ClientError::UnknownTheme(theme.to_string())));
+            }
+            state.theme_waiters.push((theme.to_string(), tx));
+        }
+        let rx = rx.map_err(|_| ClientError::RequestFailed);
+        Box::new(self.set_theme(theme).and_then(|_| rx))
+    }
+
+    /// Return the colors of a theme without switching to it, for instance
+    /// to show swatches in a theme picker. The core cannot report a theme
+    /// on demand, so unless the theme has already been applied, it is
+    /// applied temporarily with `set_theme_confirmed`, and the previous
+    /// theme is then restored, even if applying the theme failed. The
+    /// frontend therefore receives two `"theme_changed"` notifications, and
+    /// the views are restyled twice. The colors are cached, so this happens
+    /// once per theme.
+    ///
+    /// It fails with `ClientError::UnknownTheme` if the core did not
+    /// announce the theme, and with `ClientError::NoCurrentTheme` if no
+    /// theme has been applied yet, since it could then not be restored.
+    pub fn get_theme(&mut self, name: &str) -> ClientResult<ThemeSettings> {
+        let previous = {
+            let state = self.state.lock().unwrap();
+            if let Some(settings) = state.theme_settings.get(name) {
+                return Box::new(future::ok(settings.clone()));
+            }
+            if !state.themes.iter().flat_map(|themes| themes.iter()).any(|t| t == name) {
+                return Box::new(future::err(ClientError::UnknownTheme(name.to_string())));
+            }
+            match state.theme {
+                Some(ref theme) => theme.clone(),
+                None => return Box::new(future::err(ClientError::NoCurrentTheme)),
+            }
+        };
+        let mut client = self.clone();
+        let result = self.set_theme_confirmed(name).then(move |applied| {
+            client.set_theme_confirmed(&previous).then(move |restored| match (applied, restored) {
+                (Err(e), _) | (Ok(_), Err(e)) => Err(e),
+                (Ok(changed), Ok(_)) => Ok(changed.theme),
+            })
+        });
+        Box::new(result)
+    }
+
+    /// Send a `"get_config"` request, and return the effective configuration
+    /// of a view. The settings the core leaves out are filled with the
+    /// core's defaults, so that a settings panel can show actual values.
+    pub fn effective_config(&mut self, view_id: ViewId) -> RequestResult<Config> {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let result = self.request("get_config", json!({"view_id": view_id}))
+            .and_then(|result| from_value::<Config>(result).map_err(From::from));
+        Box::new(result)
+    }
+
+    /// Return the names of the edit commands the core supports, for
+    /// instance to only show these in a command palette. They are asked
+    /// with a `"supported_commands"` request. xi-core does not answer it
+    /// yet: if the core returns an error, the future resolves with the
+    /// commands of `EditMethod::ALL`, which are known to be supported.
+    pub fn supported_commands(&mut self) -> ClientResult<Vec<String>> {
+        let result = self.request("supported_commands", json!({})).then(|result| match result {
+            Ok(commands) => from_value::<Vec<String>>(commands).map_err(From::from),
+            Err(ClientError::ErrorReturned(_)) => Ok(EditMethod::ALL
+                .iter()
+                .map(|method| method.as_str().to_string())
+                .collect()),
+            Err(e) => Err(e),
+        });
+        Box::new(result)
+    }
+
+    /// Send a `"modify_user_config"` notification, to change the
+    /// configuration of the given domain.
+    /// ```ignore
+    /// {"method":"modify_user_config","params":{"changes":{"tab_size":4},
+    /// "domain":{"user_override":"view-id-1"}}}
+    /// ```
+    pub fn modify_user_config(&mut self, domain: ConfigDomain, changes: Value) -> NotifyResult {
+        if let ConfigDomain::UserOverride(view_id) = domain {
+            if let Err(e) = self.check_view(view_id) {
+                return Box::new(future::err(e));
+            }
         }
-        if let Some(path) = client_extra_dir {
-            let _ = params.insert("client_extra_dir".into(), json!(path));
+        self.notify("modify_user_config", get_config_params(&domain, changes))
+    }
+
+    /// Send several `"modify_user_config"` notifications, one per domain,
+    /// in the given order. In strict mode, all the views are checked before
+    /// anything is sent, so that the changes are either all sent or not at
+    /// all.
+    pub fn modify_user_config_multi(&mut self, changes: Vec<(ConfigDomain, Value)>) -> NotifyResult {
+        for (domain, _) in &changes {
+            if let ConfigDomain::UserOverride(view_id) = *domain {
+                if let Err(e) = self.check_view(view_id) {
+                    return Box::new(future::err(e));
+                }
+            }
         }
+        let notifications: Vec<_> = changes
+            .into_iter()
+            .map(|(domain, changes)| {
+                self.notify("modify_user_config", get_config_params(&domain, changes))
+            })
+            .collect();
+        Box::new(future::join_all(notifications).map(|_| ()))
+    }
+
+    /// Show or hide the line numbers of a view. The setting is stored in
+    /// the view's configuration, under the `"line_numbers"` key.
+    pub fn set_line_numbers(&mut self, view_id: ViewId, visible: bool) -> NotifyResult {
+        self.modify_user_config(
+            ConfigDomain::UserOverride(view_id),
+            json!({ "line_numbers": visible }),
+        )
+    }
+
+    /// Check periodically that the core is still responsive, to detect
+    /// that it died without waiting for the next user action. Every
+    /// `period`, a `"ping"` request is sent to the core. The core does not
+    /// know this method, but answering with an error is enough to show that
+    /// it is alive.
+    ///
+    /// The returned future must be spawned on the runtime. It stops at the
+    /// first ping that fails or that is not answered within `period`, and
+    /// the error is then sent to the returned receiver.
+    pub fn keepalive(
+        &self,
+        period: Duration,
+    ) -> (Box<Future<Item = (), Error = ()> + Send + Sync>, oneshot::Receiver<ClientError>) {
+        let (tx, rx) = oneshot::channel();
+        let client = self.clone();
+        let pings = Interval::new(Instant::now() + period, period)
+            .map_err(|_| ClientError::RequestFailed)
+            .for_each(move |_| {
+                let ping = client.clone().request("ping", json!({}));
+                Timeout::new(ping, period).then(|result| match result {
+                    Ok(_) => Ok(()),
+                    Err(e) => match e.into_inner() {
+                        Some(ClientError::ErrorReturned(_)) => Ok(()),
+                        Some(e) => Err(e),
+                        None => Err(ClientError::RequestFailed),
+                    },
+                })
+            })
+            .then(move |result| {
+                if let Err(e) = result {
+                    let _ = tx.send(e);
+                }
+                Ok(())
+            });
+        (Box::new(pings), rx)
+    }
+
+    /// Return the line ending of a view, as reported by the core when the
+    /// file was opened or when the setting changed. It is `None` until the
+    /// core sends it.
+    pub fn line_ending(&self, view_id: ViewId) -> Option<LineEnding> {
+        self.state.lock().unwrap().view_mut(view_id).line_ending
+    }
+
+    /// Change the line ending of a view. The setting is stored in the
+    /// view's configuration, under the `"line_ending"` key.
+    pub fn set_line_ending(&mut self, view_id: ViewId, line_ending: LineEnding) -> NotifyResult {
+        self.modify_user_config(
+            ConfigDomain::UserOverride(view_id),
+            json!({ "line_ending": line_ending }),
+        )
+    }
+
+    /// Shut down the core. The messages that are already queued are sent,
+    /// then the core's standard input is closed, which makes it exit. The
+    /// future resolves once the connection with the core is closed.
+    ///
+    /// No message can be sent with this client or any of its clones
+    /// afterwards.
+    pub fn shutdown(&mut self) -> ClientResult<()> {
+        info!(">>> shutdown");
+        Box::new(self.inner.shutdown().map_err(|_| ClientError::RequestFailed))
+    }
+
+    pub fn client_started(&mut self, config_dir: Option<&str>, client_extra_dir: Option<&str>) -> NotifyResult {
+        let params = client_started_params(config_dir, client_extra_dir);
         self.notify("client_started", params.into())
     }
 
-    pub fn start_plugin(&mut self, view_id: ViewId, name: &str) -> ClientResult<()> {
+    /// Send a `"client_started"` notification that also declares the
+    /// capabilities of the frontend. Cores that do not know about
+    /// capabilities ignore them.
+    pub fn client_started_with_capabilities(
+        &mut self,
+        config_dir: Option<&str>,
+        client_extra_dir: Option<&str>,
+        capabilities: ClientCapabilities,
+    ) -> NotifyResult {
+        let mut params = client_started_params(config_dir, client_extra_dir);
+        let _ = params.insert("capabilities".into(), json!(capabilities));
+        self.notify("client_started", params.into())
+    }
+
+    pub fn start_plugin(&mut self, view_id: ViewId, name: &str) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let _ = self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .plugins
+            .insert(name.to_string());
         let params = json!({"view_id": view_id, "plugin_name": name});
         Box::new(self.notify("start", params).and_then(|_| Ok(())))
     }
 
-    pub fn stop_plugin(&mut self, view_id: ViewId, name: &str) -> ClientResult<()> {
+    pub fn stop_plugin(&mut self, view_id: ViewId, name: &str) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let _ = self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .plugins
+            .remove(name);
         let params = json!({"view_id": view_id, "plugin_name": name});
         Box::new(self.notify("stop", params).and_then(|_| Ok(())))
     }
 
+    /// Start or stop a plugin for the given view. Nothing is sent to the
+    /// core if the plugin is already in the requested state, so that
+    /// toggling a plugin repeatedly does not send duplicate
+    /// `"start"`/`"stop"` notifications.
+    pub fn set_plugin_enabled(
+        &mut self,
+        view_id: ViewId,
+        name: &str,
+        enabled: bool,
+    ) -> NotifyResult {
+        let running = self.state
+            .lock()
+            .unwrap()
+            .view_mut(view_id)
+            .plugins
+            .contains(name);
+        match (running, enabled) {
+            (false, true) => self.start_plugin(view_id, name),
+            (true, false) => self.stop_plugin(view_id, name),
+            _ => Box::new(future::ok(())),
+        }
+    }
+
     pub fn notify_plugin(
         &mut self,
         view_id: ViewId,
         plugin: &str,
         method: &str,
         params: &Value,
-    ) -> ClientResult<()> {
+    ) -> NotifyResult {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
         let params = json!({
             "view_id": view_id,
             "receiver": plugin,
@@ -505,5 +2874,693 @@ impl Client {
         Box::new(self.notify("plugin_rpc", params).and_then(|_| Ok(())))
     }
 
-    // TODO: requests for plugin_rpc
+    /// Send a request to a plugin running for the given view, and return
+    /// its response.
+    pub fn request_plugin(
+        &mut self,
+        view_id: ViewId,
+        plugin: &str,
+        method: &str,
+        params: &Value,
+    ) -> RequestResult<Value> {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let params = json!({
+            "view_id": view_id,
+            "receiver": plugin,
+            "request": {
+                "method": method,
+                "params": params,
+            }
+        });
+        self.request("plugin_rpc", params)
+    }
+
+    /// Send several requests to a plugin running for the given view, and
+    /// return their responses in the same order. The requests are all sent
+    /// before any response is awaited, so that the plugin handles them
+    /// concurrently. The future fails if any of the requests fails.
+    pub fn plugin_batch(
+        &mut self,
+        view_id: ViewId,
+        plugin: &str,
+        requests: Vec<(String, Value)>,
+    ) -> RequestResult<Vec<Value>> {
+        if let Err(e) = self.check_view(view_id) {
+            return Box::new(future::err(e));
+        }
+        let futures: Vec<_> = requests
+            .iter()
+            .map(|(method, params)| self.request_plugin(view_id, plugin, method, params))
+            .collect();
+        Box::new(future::join_all(futures))
+    }
+
+    /// Retrieve the symbols of a document from a plugin, for instance to
+    /// implement a "go to symbol" palette. The plugin sends the columns in
+    /// UTF-16 code units: they are converted to byte columns with the text
+    /// of the lines, which are fetched with `fetch_lines` if they are not
+    /// in the line cache.
+    pub fn document_symbols(&mut self, view_id: ViewId, plugin: &str) -> RequestResult<Vec<Symbol>> {
+        let mut client = self.clone();
+        let result = self.request_plugin(view_id, plugin, "document_symbols", &json!({}))
+            .and_then(|result| from_value::<Vec<Symbol>>(result).map_err(From::from))
+            .and_then(move |symbols| {
+                let first = symbols.iter().map(|symbol| symbol.line).min().unwrap_or(0);
+                let last = symbols.iter().map(|symbol| symbol.line + 1).max().unwrap_or(0);
+                let state = client.state.clone();
+                client.fetch_lines(view_id, first, last).map(move |_| {
+                    let mut state = state.lock().unwrap();
+                    let cache = &state.view_mut(view_id).cache;
+                    symbols
+                        .into_iter()
+                        .map(|mut symbol| {
+                            if let Some(line) = cache.line(symbol.line) {
+                                symbol.col = utf16_to_byte_col(&line.text, symbol.col);
+                            }
+                            symbol
+                        })
+                        .collect()
+                })
+            });
+        Box::new(result)
+    }
+}
+
+/// Return a client whose messages are recorded instead of being sent.
+#[cfg(test)]
+fn recording_client() -> (Client, protocol::Recorder) {
+    let (recorder, inner) = protocol::Recorder::new();
+    (Client { inner, state: Default::default() }, recorder)
+}
+
+#[test]
+fn edit_envelopes() {
+    let (mut client, mut recorder) = recording_client();
+    let _ = client.scroll(ViewId(1), 0, 18);
+    let _ = client.find(ViewId(1), "foo", true, false, false);
+    let _ = client.click(ViewId(1), (3, 5));
+    let _ = client.left(ViewId(1));
+    assert_eq!(
+        recorder.messages(),
+        vec![
+            json!({"method": "edit", "params": {
+                "method": "scroll", "view_id": "view-id-1", "params": [0, 18]}}),
+            json!({"method": "edit", "params": {
+                "method": "find", "view_id": "view-id-1", "params": {
+                    "chars": "foo", "case_sensitive": true, "regex": false, "whole_words": false}}}),
+            json!({"method": "edit", "params": {
+                "method": "click", "view_id": "view-id-1", "params": [3, 5, 0, 1]}}),
+            json!({"method": "edit", "params": {
+                "method": "move_left", "view_id": "view-id-1", "params": []}}),
+        ]
+    );
+}
+
+#[test]
+fn indent_by_levels() {
+    let (mut client, mut recorder) = recording_client();
+    let _ = client.indent_by(ViewId(1), 2);
+    let _ = client.indent_by(ViewId(1), -1);
+    let methods: Vec<Value> = recorder
+        .messages()
+        .into_iter()
+        .map(|message| message["params"]["method"].clone())
+        .collect();
+    assert_eq!(methods, vec![json!("indent"), json!("indent"), json!("outdent")]);
+}
+
+#[test]
+fn request_and_plugin_envelopes() {
+    let (mut client, mut recorder) = recording_client();
+    let _ = client.new_view(Some("foo.rs".into()));
+    let _ = client.notify_plugin(ViewId(2), "syntect", "reload", &json!({"force": true}));
+    assert_eq!(
+        recorder.messages(),
+        vec![
+            json!({"id": 1, "method": "new_view", "params": {"file_path": "foo.rs"}}),
+            json!({"method": "plugin_rpc", "params": {
+                "view_id": "view-id-2",
+                "receiver": "syntect",
+                "notification": {"method": "reload", "params": {"force": true}}}}),
+        ]
+    );
+}
+
+#[test]
+fn line_numbers_config_params() {
+    let params = get_config_params(
+        &ConfigDomain::UserOverride(ViewId(1)),
+        json!({ "line_numbers": false }),
+    );
+    assert_eq!(
+        params,
+        json!({
+            "domain": {"user_override": "view-id-1"},
+            "changes": {"line_numbers": false},
+        })
+    );
+}
+
+#[test]
+fn shift_position_after_insertion() {
+    let at = Position::new(2, 4);
+    // before the insertion point
+    assert_eq!(shift_position(Position::new(1, 8), &at, "foo\nbar"), Position::new(1, 8));
+    assert_eq!(shift_position(Position::new(2, 3), &at, "foo\nbar"), Position::new(2, 3));
+    // on the same line
+    assert_eq!(shift_position(Position::new(2, 6), &at, "foo"), Position::new(2, 9));
+    assert_eq!(shift_position(Position::new(2, 6), &at, "foo\nbar"), Position::new(3, 5));
+    // on a following line
+    assert_eq!(shift_position(Position::new(5, 1), &at, "foo\nbar\n"), Position::new(7, 1));
+}
+
+#[test]
+fn edit_params_serialization_error() {
+    use std::collections::HashMap;
+
+    // JSON object keys must be strings.
+    let mut params = HashMap::new();
+    let _ = params.insert(vec![1], 2);
+    match get_edit_params(ViewId(1), "insert", Some(params)) {
+        Err(ClientError::SerializationFailed { method, .. }) => assert_eq!(method, "insert"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn replace_edit_params() {
+    let params = get_edit_params(
+        ViewId(1),
+        "replace",
+        Some(json!({"chars": "foo", "preserve_case": true})),
+    ).unwrap();
+    assert_eq!(
+        params,
+        json!({
+            "method": "replace",
+            "view_id": "view-id-1",
+            "params": {"chars": "foo", "preserve_case": true},
+        })
+    );
+
+    // replace_next and replace_all have no parameters: the selection is
+    // always moved to the next match, or to all the matches.
+    let params = get_edit_params(ViewId(1), "replace_next", None as Option<Value>).unwrap();
+    assert_eq!(params["params"], json!([]));
+}
+
+#[test]
+fn empty_edit_params() {
+    let params = get_edit_params(ViewId(1), "move_up", None as Option<Value>).unwrap();
+    assert_eq!(params["params"], json!([]));
+    let params = get_edit_params(ViewId(1), "highlight_find", None as Option<Value>).unwrap();
+    assert_eq!(params["params"], json!({}));
+    let params = get_edit_params(ViewId(1), "unknown_method", None as Option<Value>).unwrap();
+    assert_eq!(params["params"], json!([]));
+}
+
+#[test]
+fn empty_edit_params_policies() {
+    let empty = |method, policy| edit_command_params(method, None as Option<Value>, policy).unwrap();
+    assert_eq!(empty("find", EmptyParams::PerMethod), Some(json!({})));
+    assert_eq!(empty("find", EmptyParams::Array), Some(json!([])));
+    assert_eq!(empty("move_up", EmptyParams::Object), Some(json!({})));
+    assert_eq!(empty("move_up", EmptyParams::Omit), None);
+    let params = edit_command_params("scroll", Some(json!([0, 1])), EmptyParams::Omit).unwrap();
+    assert_eq!(params, Some(json!([0, 1])));
+}
+
+#[test]
+fn bare_edit_params_match_generic_ones() {
+    for method in EditMethod::ALL {
+        let bare = bare_edit_params(ViewId(3), *method);
+        let generic = get_edit_params(ViewId(3), method.as_str(), None as Option<Value>).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(bare.get()).unwrap(), generic);
+    }
+}
+
+/// Compare the generic and the specialized serialization of the movements,
+/// as when holding an arrow key. Run with
+/// `cargo test --release -- --ignored bare_edit_params_bench --nocapture`.
+#[test]
+#[ignore]
+fn bare_edit_params_bench() {
+    use protocol::message::Notification;
+
+    const ITERATIONS: u32 = 200_000;
+    let methods = [
+        EditMethod::MoveLeft,
+        EditMethod::MoveRight,
+        EditMethod::MoveUp,
+        EditMethod::MoveDown,
+    ];
+    let encode = |params: Params| {
+        let notification = Notification { method: "edit".into(), params };
+        serde_json::to_vec(&notification).unwrap().len()
+    };
+
+    let start = Instant::now();
+    let mut bytes = 0;
+    for i in 0..ITERATIONS {
+        let method = methods[i as usize % methods.len()];
+        let params = get_edit_params(ViewId(1), method.as_str(), None as Option<Value>).unwrap();
+        bytes += encode(params.into());
+    }
+    let generic = start.elapsed();
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let method = methods[i as usize % methods.len()];
+        bytes -= encode(bare_edit_params(ViewId(1), method).into());
+    }
+    let bare = start.elapsed();
+
+    assert_eq!(bytes, 0);
+    println!("generic: {:?}, bare: {:?} for {} movements", generic, bare, ITERATIONS);
+}
+
+#[test]
+fn centered_range_clamped_at_top() {
+    assert_eq!(centered_range(100, 20), (90, 110));
+    assert_eq!(centered_range(100, 21), (90, 111));
+    assert_eq!(centered_range(3, 20), (0, 20));
+    assert_eq!(centered_range(0, 0), (0, 0));
+}
+
+#[test]
+fn revealed_range_biased_to_start() {
+    // The range fits and is centered.
+    assert_eq!(revealed_range(50, 59, 20), (45, 65));
+    assert_eq!(revealed_range(59, 50, 20), (45, 65));
+    assert_eq!(revealed_range(2, 5, 20), (0, 20));
+    // The range does not fit, its start is shown.
+    assert_eq!(revealed_range(50, 100, 20), (50, 70));
+}
+
+#[test]
+fn move_lines_helpers() {
+    let caret = SelRegion::caret(Position::new(3, 2));
+    assert_eq!(selected_lines(&[caret.clone()]), Some((3, 3)));
+    // a selection that ends at the start of a line does not include it
+    let lines = SelRegion::new(Position::new(5, 0), Position::new(3, 0));
+    assert_eq!(selected_lines(&[caret, lines]), Some((3, 4)));
+    assert_eq!(selected_lines(&[]), None);
+
+    assert_eq!(swap_lines(&["a", "b", "c"], true), "b\nc\na");
+    assert_eq!(swap_lines(&["a", "b", "c"], false), "c\na\nb");
+}
+
+#[test]
+fn line_deletion_ranges() {
+    let range = |start: (u64, u64), end: (u64, u64)| Some((start.into(), end.into()));
+    assert_eq!(line_deletion_range(1, 2, 5, Some(3), 4), range((1, 0), (3, 0)));
+    assert_eq!(line_deletion_range(3, 4, 5, Some(3), 4), range((2, 3), (4, 4)));
+    assert_eq!(line_deletion_range(0, 0, 1, None, 4), range((0, 0), (0, 4)));
+    assert_eq!(line_deletion_range(4, 4, 5, None, 4), None);
+}
+
+#[test]
+fn sel_movements() {
+    let up = |mode| {
+        sel_movement(EditMethod::MoveUpAndModifySelection, Some(EditMethod::AddSelectionAbove), mode)
+    };
+    assert_eq!(up(SelectionMode::default()), Some(EditMethod::MoveUpAndModifySelection));
+    assert_eq!(up(SelectionMode::AddCaret), Some(EditMethod::AddSelectionAbove));
+    let left = sel_movement(EditMethod::MoveLeftAndModifySelection, None, SelectionMode::AddCaret);
+    assert_eq!(left, None);
+}
+
+#[test]
+fn locate_offsets() {
+    let lines = ["ab\n", "cde\n", "f"];
+    let locate = |offset| {
+        let mut acc = (offset, (0, 0));
+        for (line_nb, text) in lines.iter().enumerate() {
+            if !locate_offset(&mut acc, line_nb as u64, text) {
+                break;
+            }
+        }
+        acc.1
+    };
+    assert_eq!(locate(0), (0, 0));
+    assert_eq!(locate(2), (0, 2));
+    assert_eq!(locate(3), (1, 0));
+    assert_eq!(locate(7), (2, 0));
+    assert_eq!(locate(8), (2, 1));
+    assert_eq!(locate(100), (2, 1));
+}
+
+#[test]
+fn split_paste_chunks() {
+    assert_eq!(split_chunks("abcdefg", 3), vec!["abc", "def", "g"]);
+    // "é" is 2 bytes long and is not split
+    assert_eq!(split_chunks("aébc", 2), vec!["a", "é", "bc"]);
+    assert_eq!(split_chunks("éé", 1), vec!["é", "é"]);
+    assert!(split_chunks("", 3).is_empty());
+}
+
+#[test]
+fn word_bounds_at_columns() {
+    let text = "let foo_bar = élan;";
+    assert_eq!(word_bounds(text, 5), Some((4, 11)));
+    assert_eq!(word_bounds(text, 4), Some((4, 11)));
+    assert_eq!(word_bounds(text, 11), Some((4, 11)));
+    assert_eq!(word_bounds(text, 12), None);
+    assert_eq!(word_bounds(text, 14), Some((14, 19)));
+    assert_eq!(word_bounds(text, 15), None);
+    assert_eq!(word_bounds(text, 100), None);
+}
+
+#[test]
+fn find_matches_reported_once() {
+    use structs::FindStatus;
+
+    let status = |lines: Vec<u64>| FindStatusChanged {
+        view_id: ViewId(1),
+        queries: vec![FindStatus {
+            id: 1,
+            chars: Some("a".into()),
+            case_sensitive: None,
+            is_regex: None,
+            whole_words: None,
+            matches: lines.len() as u64,
+            lines,
+        }],
+    };
+    let lines = |matches: Vec<FindMatch>| matches.iter().map(|m| m.line).collect::<Vec<_>>();
+    let mut reported = HashMap::new();
+    assert_eq!(lines(new_find_matches(&mut reported, &status(vec![1, 3, 3]))), vec![1, 3, 3]);
+    assert_eq!(lines(new_find_matches(&mut reported, &status(vec![1, 3, 3, 8]))), vec![8]);
+    assert_eq!(lines(new_find_matches(&mut reported, &status(vec![2]))), vec![2]);
+}
+
+#[test]
+fn toggle_block_comments() {
+    assert_eq!(block_comment_delimiters("Rust"), Some(("/*", "*/")));
+    assert_eq!(block_comment_delimiters("Python"), None);
+    assert_eq!(toggle_block_comment_text("a + b", "/*", "*/"), "/* a + b */");
+    assert_eq!(toggle_block_comment_text("/* a + b */", "/*", "*/"), "a + b");
+    assert_eq!(toggle_block_comment_text("  /*a*/\n", "/*", "*/"), "  a\n");
+    assert_eq!(toggle_block_comment_text("/*/", "/*", "*/"), "/* /*/ */");
+    assert_eq!(toggle_block_comment_text("<!-- x -->", "<!--", "-->"), "x");
+}
+
+#[test]
+fn subword_boundaries() {
+    assert_eq!(next_subword_boundary("fooBarBaz", 0), 3);
+    assert_eq!(next_subword_boundary("fooBarBaz", 3), 6);
+    assert_eq!(next_subword_boundary("foo_bar", 3), 7);
+    assert_eq!(next_subword_boundary("HTTPServer", 0), 4);
+    assert_eq!(next_subword_boundary("  x", 0), 3);
+    assert_eq!(next_subword_boundary("foo", 3), 3);
+    assert_eq!(prev_subword_boundary("fooBarBaz", 9), 6);
+    assert_eq!(prev_subword_boundary("HTTPServer", 10), 4);
+    assert_eq!(prev_subword_boundary("HTTPServer", 4), 0);
+    assert_eq!(prev_subword_boundary("foo_bar", 4), 0);
+    assert_eq!(prev_subword_boundary("é_à", 5), 3);
+}
+
+#[test]
+fn shift_column_across_lines() {
+    let lines = ["ab", "é", ""];
+    let text = |line: u64| lines.get(line as usize).cloned();
+    assert_eq!(shift_column(&text, Position::new(0, 0), 2), Position::new(0, 2));
+    assert_eq!(shift_column(&text, Position::new(0, 1), 2), Position::new(1, 0));
+    assert_eq!(shift_column(&text, Position::new(1, 0), 1), Position::new(1, 2));
+    assert_eq!(shift_column(&text, Position::new(1, 2), -2), Position::new(0, 2));
+    assert_eq!(shift_column(&text, Position::new(0, 1), i64::MIN), Position::new(0, 0));
+    assert_eq!(shift_column(&text, Position::new(1, 0), i64::MAX), Position::new(2, 0));
+    assert_eq!(shift_column(|_| None, Position::new(4, 3), 5), Position::new(4, 3));
+}
+
+#[test]
+fn move_horizontal_sets_the_selections_at_once() {
+    let (mut client, mut recorder) = recording_client();
+    cache_lines(&client, json!([{"op": "ins", "n": 2, "lines": [
+        {"text": "foo\n", "cursor": [1]},
+        {"text": "bar", "cursor": [0]},
+    ]}]));
+    let _ = client.move_horizontal(ViewId(1), 3, false);
+    let messages = recorder.messages();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["params"]["params"], json!({"line": 1, "col": 0, "ty": "point_select"}));
+    assert_eq!(messages[1]["params"]["params"], json!({"line": 1, "col": 3, "ty": "toggle_sel"}));
+}
+
+#[test]
+fn client_and_futures_are_send_and_sync() {
+    use receiver::ClientReceiver;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Client>();
+    assert_send_sync::<ClientResult<Value>>();
+    assert_send_sync::<NotifyResult>();
+    assert_send_sync::<ClientReceiver>();
+}
+
+#[test]
+fn insert_grapheme_sends_one_insert() {
+    let (mut client, mut recorder) = recording_client();
+    let _ = client.insert_grapheme(ViewId(1), "\u{1f44d}\u{1f3fd}");
+    let messages = recorder.messages();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["params"]["method"], json!("insert"));
+    assert_eq!(messages[0]["params"]["params"], json!({"chars": "\u{1f44d}\u{1f3fd}"}));
+}
+
+#[cfg(test)]
+fn cache_lines(client: &Client, ops: Value) {
+    let update: Update = from_value(json!({
+        "view_id": "view-id-1",
+        "update": {"ops": ops, "pristine": true}
+    })).unwrap();
+    client.state.lock().unwrap().handle_update(&update);
+}
+
+#[test]
+fn document_text_from_cached_lines() {
+    let (mut client, mut recorder) = recording_client();
+    cache_lines(&client, json!([{"op": "ins", "n": 2, "lines": [{"text": "foo\n"}, {"text": "bar"}]}]));
+    assert_eq!(client.get_document_text(ViewId(1)).wait().unwrap(), "foo\nbar");
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn document_text_requests_missing_lines() {
+    let (mut client, mut recorder) = recording_client();
+    cache_lines(&client, json!([
+        {"op": "ins", "n": 1, "lines": [{"text": "foo\n"}]},
+        {"op": "invalidate", "n": 2},
+    ]));
+    let mut text = executor::spawn(client.get_document_text(ViewId(1)));
+    assert_eq!(poll_once(&mut text).unwrap(), Async::NotReady);
+    let messages = recorder.messages();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["params"]["method"], json!("request_lines"));
+    assert_eq!(messages[0]["params"]["params"], json!([1, 3]));
+    cache_lines(&client, json!([
+        {"op": "copy", "n": 1},
+        {"op": "ins", "n": 2, "lines": [{"text": "bar\n"}, {"text": "baz"}]},
+    ]));
+    assert_eq!(poll_once(&mut text).unwrap(), Async::Ready("foo\nbar\nbaz".to_string()));
+}
+
+#[test]
+fn debug_contents_wait_for_the_first_update() {
+    let (mut client, mut recorder) = recording_client();
+    let mut contents = executor::spawn(client.debug_get_contents(ViewId(1)));
+    assert_eq!(poll_once(&mut contents).unwrap(), Async::NotReady);
+    cache_lines(&client, json!([{"op": "ins", "n": 2, "lines": [{"text": "foo\n"}, {"text": "bar"}]}]));
+    assert_eq!(poll_once(&mut contents).unwrap(), Async::Ready("foo\nbar".to_string()));
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[cfg(test)]
+struct NoopNotify;
+
+#[cfg(test)]
+impl executor::Notify for NoopNotify {
+    fn notify(&self, _id: usize) {}
+}
+
+/// Poll a future once, as an executor would, so that a test can feed it
+/// the messages it waits for between two polls.
+#[cfg(test)]
+fn poll_once<F: Future>(task: &mut executor::Spawn<F>) -> Poll<F::Item, F::Error> {
+    task.poll_future_notify(&::std::sync::Arc::new(NoopNotify), 0)
+}
+
+#[test]
+fn select_all_confirmed_uses_the_cached_last_line() {
+    let (mut client, mut recorder) = recording_client();
+    let mut region = executor::spawn(client.select_all_confirmed(ViewId(1)));
+    assert_eq!(poll_once(&mut region).unwrap(), Async::NotReady);
+    let messages = recorder.messages();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["params"]["method"], json!("select_all"));
+    cache_lines(&client, json!([{"op": "ins", "n": 2, "lines": [{"text": "foo\n"}, {"text": "ba"}]}]));
+    assert_eq!(
+        poll_once(&mut region).unwrap(),
+        Async::Ready(SelRegion::new(Position::new(0, 0), Position::new(1, 2)))
+    );
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn select_all_confirmed_uses_the_selection_annotation() {
+    let (mut client, mut recorder) = recording_client();
+    let mut region = executor::spawn(client.select_all_confirmed(ViewId(1)));
+    assert_eq!(poll_once(&mut region).unwrap(), Async::NotReady);
+    let messages = recorder.messages();
+    assert_eq!(messages[0]["params"]["method"], json!("select_all"));
+    let update: Update = from_value(json!({
+        "view_id": "view-id-1",
+        "update": {"ops": [], "pristine": true, "annotations": [
+            {"type": "selection", "n": 1, "ranges": [[0, 0, 41, 7]], "payloads": null},
+        ]}
+    })).unwrap();
+    client.state.lock().unwrap().handle_update(&update);
+    assert_eq!(
+        poll_once(&mut region).unwrap(),
+        Async::Ready(SelRegion::new(Position::new(0, 0), Position::new(41, 7)))
+    );
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn select_all_confirmed_when_all_is_selected() {
+    let (mut client, mut recorder) = recording_client();
+    cache_lines(&client, json!([{"op": "ins", "n": 2, "lines": [
+        {"text": "foo\n", "styles": [0, 4, 0]},
+        {"text": "ba", "cursor": [2], "styles": [0, 2, 0]},
+    ]}]));
+    assert_eq!(
+        client.select_all_confirmed(ViewId(1)).wait().unwrap(),
+        SelRegion::new(Position::new(0, 0), Position::new(1, 2))
+    );
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn get_line_from_the_cache() {
+    let (mut client, mut recorder) = recording_client();
+    cache_lines(&client, json!([{"op": "ins", "n": 2, "lines": [{"text": "foo bar\n"}, {"text": "baz"}]}]));
+    assert_eq!(client.get_line(ViewId(1), 1).wait().unwrap(), "baz");
+    assert_eq!(
+        client.word_at(ViewId(1), 0, 5).wait().unwrap(),
+        Some(("bar".to_string(), 4, 7))
+    );
+    match client.get_line(ViewId(1), 2).wait() {
+        Err(ClientError::LineOutOfRange(2)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn set_theme_confirmed_rejects_unknown_themes() {
+    let (mut client, mut recorder) = recording_client();
+    client.state.lock().unwrap().themes = Some(vec!["InspiredGitHub".into()]);
+    match client.set_theme_confirmed("InspiredGitHib").wait() {
+        Err(ClientError::UnknownTheme(ref name)) if name == "InspiredGitHib" => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert!(client.state.lock().unwrap().theme_waiters.is_empty());
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn get_theme_needs_a_theme_to_restore() {
+    let (mut client, mut recorder) = recording_client();
+    client.state.lock().unwrap().themes = Some(vec!["InspiredGitHub".into()]);
+    match client.get_theme("Solarized (dark)").wait() {
+        Err(ClientError::UnknownTheme(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    match client.get_theme("InspiredGitHub").wait() {
+        Err(ClientError::NoCurrentTheme) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert_eq!(recorder.messages(), Vec::<Value>::new());
+}
+
+#[test]
+fn sel_movements_cannot_always_add_a_caret() {
+    let (mut client, mut recorder) = recording_client();
+    client.set_default_selection_mode(SelectionMode::AddCaret);
+    match client.left_sel(ViewId(1), None).wait() {
+        Err(ClientError::CannotAddCaret(ref method)) if method == "move_left_and_modify_selection" => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let _ = client.down_sel(ViewId(1), None);
+    let _ = client.down_sel(ViewId(1), Some(SelectionMode::Extend));
+    let methods: Vec<Value> = recorder
+        .messages()
+        .into_iter()
+        .map(|message| message["params"]["method"].clone())
+        .collect();
+    assert_eq!(methods, vec![json!("add_selection_below"), json!("move_down_and_modify_selection")]);
+}
+
+#[test]
+fn document_symbols_have_byte_columns() {
+    let (mut client, mut recorder) = recording_client();
+    cache_lines(&client, json!([{"op": "ins", "n": 1, "lines": [{"text": "é = main\n"}]}]));
+    let symbols = client.document_symbols(ViewId(1), "lsp");
+    let messages = recorder.messages();
+    assert_eq!(messages[0]["method"], json!("plugin_rpc"));
+    recorder.respond(
+        messages[0]["id"].as_u64().unwrap(),
+        json!([{"name": "main", "kind": "function", "line": 0, "col": 4}]),
+    );
+    assert_eq!(symbols.wait().unwrap()[0].col, 5);
+}
+
+#[test]
+fn find_selection_keeps_the_kill_buffer() {
+    let (mut client, mut recorder) = recording_client();
+    client.state.lock().unwrap().kill_buffer = "killed".to_string();
+    let mut found = executor::spawn(client.find_selection(ViewId(1)));
+    assert_eq!(poll_once(&mut found).unwrap(), Async::NotReady);
+    let messages = recorder.messages();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["params"]["method"], json!("copy"));
+    recorder.respond(messages[0]["id"].as_u64().unwrap(), json!("foo"));
+    assert_eq!(poll_once(&mut found).unwrap(), Async::NotReady);
+    let messages = recorder.messages();
+    assert_eq!(messages[0]["params"]["method"], json!("find"));
+    assert_eq!(messages[0]["params"]["params"]["chars"], json!("foo"));
+    assert_eq!(poll_once(&mut found).unwrap(), Async::Ready(()));
+    assert_eq!(client.state.lock().unwrap().kill_buffer, "killed");
+}
+
+#[test]
+fn save_all_waits_for_pristine_updates() {
+    use tokio::runtime::current_thread::Runtime;
+
+    let (mut client, mut recorder) = recording_client();
+    let state = client.state.clone();
+    let set_pristine = |view_id: &str, pristine: bool| {
+        let update: Update = from_value(json!({
+            "view_id": view_id,
+            "update": {"ops": [], "pristine": pristine}
+        })).unwrap();
+        state.lock().unwrap().handle_update(&update);
+    };
+    set_pristine("view-id-1", false);
+    set_pristine("view-id-2", false);
+    let saves = client.save_all(
+        &[(ViewId(1), "foo.rs"), (ViewId(2), "bar.rs")],
+        Duration::from_millis(10),
+    );
+    assert_eq!(recorder.messages().len(), 2);
+    set_pristine("view-id-1", true);
+    let results = Runtime::new().unwrap().block_on(saves).unwrap();
+    assert!(results[0].is_ok());
+    match results[1] {
+        Err(ClientError::SaveNotConfirmed(ViewId(2))) => {}
+        ref other => panic!("unexpected result: {:?}", other),
+    }
 }
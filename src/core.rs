@@ -1,6 +1,6 @@
-use futures::{Future, Poll, Stream};
+use futures::{Async, Future, Poll, Stream};
 use bytes::BytesMut;
-use protocol::Endpoint;
+use protocol::{ChannelConfig, Endpoint};
 use client::Client;
 use std::io::{self, Read, Write};
 use std::process::Command;
@@ -9,14 +9,18 @@ use tokio;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_codec::{FramedRead, Decoder};
 use tokio_process::{Child, ChildStderr, ChildStdin, ChildStdout, CommandExt};
-use frontend::{Frontend, FrontendBuilder};
+use frontend::{Frontend, FrontendBuilder, FrontendService};
+use receiver::{self, ClientReceiver, ClientSender};
 use std::clone::Clone;
+use structs::ConnectionEvent;
 
 struct Core {
     #[allow(dead_code)]
     core: Child,
     stdout: ChildStdout,
-    stdin: ChildStdin,
+    // Dropping the core's stdin makes it exit. It is set to `None` when the
+    // stream is shut down.
+    stdin: Option<ChildStdin>,
 }
 
 impl Read for Core {
@@ -32,24 +36,52 @@ impl AsyncRead for Core {
     }
 }
 
+impl Core {
+    fn stdin(&mut self) -> io::Result<&mut ChildStdin> {
+        self.stdin
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "xi-core stdin is closed"))
+    }
+}
+
 impl Write for Core {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stdin.write(buf)
+        self.stdin()?.write(buf)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.stdin.flush()
+        self.stdin()?.flush()
     }
 }
 
 impl AsyncWrite for Core {
     fn shutdown(&mut self) -> Poll<(), io::Error> {
-        self.stdin.shutdown()
+        if let Some(ref mut stdin) = self.stdin {
+            if let Async::NotReady = stdin.shutdown()? {
+                return Ok(Async::NotReady);
+            }
+        }
+        self.stdin = None;
+        Ok(Async::Ready(()))
     }
 }
 
 /// Start Xi core, and return a client and a stream of Xi's stderr lines.
 pub fn spawn<B, F>(executable: &str, builder: B) -> (Client, CoreStderr)
+where
+    B: FrontendBuilder<F> + 'static,
+    F: Frontend + 'static + Send,
+{
+    spawn_with_channel_config(executable, builder, ChannelConfig::default())
+}
+
+/// Start Xi core like `spawn`, with a custom configuration for the channel
+/// the client uses to send messages to the core.
+pub fn spawn_with_channel_config<B, F>(
+    executable: &str,
+    builder: B,
+    config: ChannelConfig,
+) -> (Client, CoreStderr)
 where
     B: FrontendBuilder<F> + 'static,
     F: Frontend + 'static + Send,
@@ -68,19 +100,40 @@ where
     let core = Core {
         core: xi_core,
         stdout,
-        stdin,
+        stdin: Some(stdin),
     };
 
     let mut endpoint = Endpoint::new(core);
-    let client = Client(endpoint.set_client());
-    let service = builder.build(client.clone());
-    endpoint.set_server(service);
+    let client = Client::new(endpoint.set_client(config));
+    let frontend = builder.build(client.clone());
+    endpoint.set_server(FrontendService::new(frontend, client.state()));
+    let state = client.state();
+    state.lock().unwrap().handle_connection_event(ConnectionEvent::Connected);
     ::std::thread::spawn(move || {
-        tokio::run(endpoint.map_err(|_| ()));
+        tokio::run(endpoint.then(move |result| {
+            let reason = match result {
+                Ok(()) => "connection closed".to_string(),
+                Err(e) => e.to_string(),
+            };
+            let event = ConnectionEvent::Disconnected { reason };
+            state.lock().unwrap().handle_connection_event(event);
+            Ok(())
+        }));
     });
     (client, CoreStderr::new(stderr))
 }
 
+/// Start Xi core, and return the two halves of the connection: a
+/// `ClientSender` to send messages to the core, and a `ClientReceiver` to
+/// receive its notifications. Requests from the core, like
+/// `"measure_width"`, are answered with an error: use `spawn` with a
+/// `Frontend` to handle them.
+pub fn spawn_split(executable: &str) -> (ClientSender, ClientReceiver, CoreStderr) {
+    let (builder, receiver) = receiver::channel();
+    let (sender, stderr) = spawn(executable, builder);
+    (sender, receiver, stderr)
+}
+
 pub struct LineCodec;
 
 // straight from
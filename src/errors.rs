@@ -2,6 +2,7 @@ use std::fmt;
 use std::error;
 use serde_json::Value;
 use serde_json::error::Error as SerdeError;
+use structs::ViewId;
 
 #[derive(Debug)]
 pub enum ClientError {
@@ -14,8 +15,36 @@ pub enum ClientError {
     /// serialization error.
     SerializeFailed(SerdeError),
 
+    /// The parameters of an edit command could not be serialized.
+    SerializationFailed { method: String, source: SerdeError },
+
     /// The server response is an error
     ErrorReturned(Value),
+
+    /// A message could not be sent because the channel to the core is full.
+    /// This only happens with `OverflowPolicy::Error`.
+    ChannelFull,
+
+    /// In strict mode, a message was about to be sent for a view that has
+    /// not been opened by the client, or that has been closed.
+    UnknownView(ViewId),
+
+    /// A line was requested past the end of the document.
+    LineOutOfRange(u64),
+
+    /// A theme is not among the themes announced by the core.
+    UnknownTheme(String),
+
+    /// No theme has been applied yet, so there is none to restore.
+    NoCurrentTheme,
+
+    /// A movement was asked to add a caret, which xi-core cannot do for
+    /// this movement.
+    CannotAddCaret(String),
+
+    /// The core did not mark a view as pristine in time after a save, for
+    /// instance because the file could not be written.
+    SaveNotConfirmed(ViewId),
 }
 
 impl fmt::Display for ClientError {
@@ -31,6 +60,18 @@ impl fmt::Display for ClientError {
             ClientError::SerializeFailed(ref e) => {
                 write!(f, "failed to serialize a message: {}", e)
             }
+            ClientError::SerializationFailed { ref method, ref source } => {
+                write!(f, "failed to serialize the parameters of \"{}\": {}", method, source)
+            }
+            ClientError::ChannelFull => write!(f, "The channel to the core is full"),
+            ClientError::UnknownView(ref view_id) => write!(f, "Unknown view {:?}", view_id),
+            ClientError::LineOutOfRange(line) => write!(f, "Line {} is out of range", line),
+            ClientError::UnknownTheme(ref name) => write!(f, "Unknown theme {}", name),
+            ClientError::NoCurrentTheme => write!(f, "No theme has been applied yet"),
+            ClientError::CannotAddCaret(ref method) => write!(f, "\"{}\" cannot add a caret", method),
+            ClientError::SaveNotConfirmed(ref view_id) => {
+                write!(f, "The save of {:?} was not confirmed", view_id)
+            }
         }
     }
 }
@@ -42,14 +83,22 @@ impl error::Error for ClientError {
             ClientError::RequestFailed => "Failed to send a request or receive its response",
             ClientError::ErrorReturned(_) => "The core answered with an error",
             ClientError::SerializeFailed(_) => "failed to serialize message",
+            ClientError::SerializationFailed { .. } => "failed to serialize the parameters",
+            ClientError::ChannelFull => "The channel to the core is full",
+            ClientError::UnknownView(_) => "Unknown view",
+            ClientError::LineOutOfRange(_) => "Line out of range",
+            ClientError::UnknownTheme(_) => "Unknown theme",
+            ClientError::NoCurrentTheme => "No theme has been applied yet",
+            ClientError::CannotAddCaret(_) => "The movement cannot add a caret",
+            ClientError::SaveNotConfirmed(_) => "The save was not confirmed",
         }
     }
 
     fn cause(&self) -> Option<&error::Error> {
-        if let ClientError::SerializeFailed(ref serde_error) = *self {
-            Some(serde_error)
-        } else {
-            None
+        match *self {
+            ClientError::SerializeFailed(ref serde_error)
+            | ClientError::SerializationFailed { source: ref serde_error, .. } => Some(serde_error),
+            _ => None,
         }
     }
 }
@@ -3,17 +3,20 @@ use protocol::Service;
 use futures::{future, Future};
 use serde_json::{from_value, Value};
 use structs::{
-    AvailablePlugins, PluginStarted, PluginStoped,
+    AvailablePlugins, AvailableLanguages, AvailableThemes, FindStatusChanged,
+    ReplaceStatusChanged, LanguageChanged, Alert, PluginStarted, PluginStoped, PluginStatus,
     Update, ScrollTo, UpdateCmds, Style, ThemeChanged,
-    ConfigChanged
-
+    ConfigChanged, MeasureWidth,
 };
 use client::Client;
+use state::{self, SharedState};
 
 pub type ServerResult<T> = Box<Future<Item = T, Error = ServerError>>;
 
 /// The `Frontend` trait must be implemented by clients. It defines how the
-/// client handles notifications and requests coming from `xi-core`.
+/// client handles notifications and requests coming from `xi-core`. The
+/// methods with a default implementation can be left out: the
+/// notifications are then ignored, and the requests answered with an error.
 pub trait Frontend {
     /// handle `"updates"` notifications from `xi-core`
     fn update(&mut self, update: Update) -> ServerResult<()>;
@@ -27,12 +30,42 @@ pub trait Frontend {
     fn update_cmds(&mut self, plugins: UpdateCmds) -> ServerResult<()>;
     /// handle `"plugin_started"` notifications from `xi-core`
     fn plugin_started(&mut self, plugins: PluginStarted) -> ServerResult<()>;
-    /// handle `"plugin_stoped"` notifications from `xi-core`
+    /// handle `"plugin_stopped"` notifications from `xi-core`
     fn plugin_stoped(&mut self, plugin: PluginStoped) -> ServerResult<()>;
     /// handle `"config_changed"` notifications from `xi-core`
     fn config_changed(&mut self, config: ConfigChanged) -> ServerResult<()>;
     /// handle `"theme_changed"` notifications from `xi-core`
     fn theme_changed(&mut self, theme: ThemeChanged) -> ServerResult<()>;
+    /// handle `"measure_width"` requests from `xi-core`. The result must
+    /// contain one vector of widths per item of the request. By default,
+    /// the request is answered with an error.
+    fn measure_width(&mut self, _request: MeasureWidth) -> ServerResult<Vec<Vec<f32>>> {
+        Box::new(future::err(ServerError::from("measure_width is not implemented by the frontend")))
+    }
+    /// handle `"available_languages"` notifications from `xi-core`
+    fn available_languages(&mut self, _languages: AvailableLanguages) -> ServerResult<()> {
+        Box::new(future::ok(()))
+    }
+    /// handle `"available_themes"` notifications from `xi-core`
+    fn available_themes(&mut self, _themes: AvailableThemes) -> ServerResult<()> {
+        Box::new(future::ok(()))
+    }
+    /// handle `"find_status"` notifications from `xi-core`
+    fn find_status(&mut self, _status: FindStatusChanged) -> ServerResult<()> {
+        Box::new(future::ok(()))
+    }
+    /// handle `"replace_status"` notifications from `xi-core`
+    fn replace_status(&mut self, _status: ReplaceStatusChanged) -> ServerResult<()> {
+        Box::new(future::ok(()))
+    }
+    /// handle `"language_changed"` notifications from `xi-core`
+    fn language_changed(&mut self, _language: LanguageChanged) -> ServerResult<()> {
+        Box::new(future::ok(()))
+    }
+    /// handle `"alert"` notifications from `xi-core`
+    fn alert(&mut self, _alert: Alert) -> ServerResult<()> {
+        Box::new(future::ok(()))
+    }
 }
 
 /// A builder for the type `F` that implement the `Frontend` trait.
@@ -43,7 +76,21 @@ where
     fn build(self, client: Client) -> F;
 }
 
-impl<F: Frontend + Send> Service for F {
+/// The service that dispatches the messages sent by `xi-core` to a
+/// `Frontend`. Before being forwarded, the notifications are used to keep
+/// the state shared with the `Client` up to date.
+pub struct FrontendService<F: Frontend> {
+    frontend: F,
+    state: SharedState,
+}
+
+impl<F: Frontend> FrontendService<F> {
+    pub fn new(frontend: F, state: SharedState) -> Self {
+        FrontendService { frontend, state }
+    }
+}
+
+impl<F: Frontend + Send> Service for FrontendService<F> {
     type T = Value;
     type E = Value;
     type Error = ServerError;
@@ -53,10 +100,31 @@ impl<F: Frontend + Send> Service for F {
         method: &str,
         params: Value,
     ) -> Box<Future<Item = Result<Self::T, Self::E>, Error = Self::Error>> {
-        // AFAIK the core does not send any request to frontends yet
-        // We should return an ServerError here
         info!("<<< request: method={}, params={}", method, &params);
-        unimplemented!();
+        match method {
+            "measure_width" => match from_value::<MeasureWidth>(params) {
+                Ok(request) => Box::new(
+                    self.frontend
+                        .measure_width(request)
+                        .map(|widths| Ok(json!(widths))),
+                ),
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e))),
+            },
+
+            // Requests forwarded by the core on behalf of plugins. The
+            // requests nobody handles are answered with an error, so that
+            // the plugin does not wait for a response forever.
+            _ => match state::handle_request(&self.state, method, params) {
+                Some(result) => Box::new(future::ok(result)),
+                None => {
+                    warn!("no handler for request {}", method);
+                    Box::new(future::ok(Err(json!({
+                        "code": -32601,
+                        "message": format!("unknown method {}", method),
+                    }))))
+                }
+            },
+        }
     }
 
     fn handle_notification(
@@ -65,43 +133,102 @@ impl<F: Frontend + Send> Service for F {
         params: Value,
     ) -> Box<Future<Item = (), Error = Self::Error>> {
         info!("<<< notification: method={}, params={}", method, &params);
+        self.state.lock().unwrap().handle_plugin_notification(method, &params);
         match method {
             "update" => match from_value::<Update>(params) {
-                Ok(update) => self.update(update),
+                Ok(update) => {
+                    self.state.lock().unwrap().handle_update(&update);
+                    self.frontend.update(update)
+                }
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e))),
             },
 
             "scroll_to" => match from_value::<ScrollTo>(params) {
-                Ok(scroll_to) => self.scroll_to(scroll_to),
+                Ok(scroll_to) => self.frontend.scroll_to(scroll_to),
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e))),
             },
 
             "def_style" => match from_value::<Style>(params) {
-                Ok(style) => self.def_style(style),
+                Ok(style) => {
+                    self.state.lock().unwrap().handle_def_style(&style);
+                    self.frontend.def_style(style)
+                }
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e))),
             },
             "available_plugins" => match from_value::<AvailablePlugins>(params) {
-                Ok(plugins) => self.available_plugins(plugins),
+                Ok(plugins) => self.frontend.available_plugins(plugins),
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
             },
             "plugin_started" => match from_value::<PluginStarted>(params) {
-                Ok(plugin) => self.plugin_started(plugin),
+                Ok(plugin) => {
+                    let status = PluginStatus::from(plugin.clone());
+                    self.state.lock().unwrap().handle_plugin_status(&status);
+                    self.frontend.plugin_started(plugin)
+                }
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
             },
-            "plugin_stoped" => match from_value::<PluginStoped>(params) {
-                Ok(plugin) => self.plugin_stoped(plugin),
+            // xi-core sends "plugin_stopped", but older versions used to
+            // send "plugin_stoped".
+            "plugin_stopped" | "plugin_stoped" => match from_value::<PluginStoped>(params) {
+                Ok(plugin) => {
+                    let status = PluginStatus::from(plugin.clone());
+                    self.state.lock().unwrap().handle_plugin_status(&status);
+                    self.frontend.plugin_stoped(plugin)
+                }
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
             },
             "update_cmds" => match from_value::<UpdateCmds>(params) {
-                Ok(cmds) => self.update_cmds(cmds),
+                Ok(cmds) => self.frontend.update_cmds(cmds),
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e))),
             },
             "config_changed" => match from_value::<ConfigChanged>(params) {
-                Ok(config) => self.config_changed(config),
+                Ok(config) => {
+                    self.state.lock().unwrap().handle_config_changed(&config);
+                    self.frontend.config_changed(config)
+                }
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
             },
             "theme_changed" => match from_value::<ThemeChanged>(params) {
-                Ok(theme) => self.theme_changed(theme),
+                Ok(theme) => {
+                    self.state.lock().unwrap().handle_theme_changed(&theme);
+                    self.frontend.theme_changed(theme)
+                }
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
+            },
+            "available_languages" => match from_value::<AvailableLanguages>(params) {
+                Ok(languages) => {
+                    self.state.lock().unwrap().handle_available_languages(&languages);
+                    self.frontend.available_languages(languages)
+                }
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
+            },
+            "available_themes" => match from_value::<AvailableThemes>(params) {
+                Ok(themes) => {
+                    self.state.lock().unwrap().handle_available_themes(&themes);
+                    self.frontend.available_themes(themes)
+                }
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
+            },
+            "find_status" => match from_value::<FindStatusChanged>(params) {
+                Ok(status) => {
+                    self.state.lock().unwrap().handle_find_status(&status);
+                    self.frontend.find_status(status)
+                }
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
+            },
+            "replace_status" => match from_value::<ReplaceStatusChanged>(params) {
+                Ok(status) => self.frontend.replace_status(status),
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
+            },
+            "language_changed" => match from_value::<LanguageChanged>(params) {
+                Ok(language) => {
+                    self.state.lock().unwrap().handle_language_changed(&language);
+                    self.frontend.language_changed(language)
+                }
+                Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
+            },
+            "alert" => match from_value::<Alert>(params) {
+                Ok(alert) => self.frontend.alert(alert),
                 Err(e) => Box::new(future::err(ServerError::DeserializeFailed(e)))
             },
 
@@ -60,6 +60,27 @@
 //!    fn theme_changed(&mut self, theme: ThemeChanged) -> ServerResult<()> {
 //!        Box::new(future::ok(()))
 //!    }
+//!    fn measure_width(&mut self, request: MeasureWidth) -> ServerResult<Vec<Vec<f32>>> {
+//!        Box::new(future::ok(Vec::new()))
+//!    }
+//!    fn available_languages(&mut self, languages: AvailableLanguages) -> ServerResult<()> {
+//!        Box::new(future::ok(()))
+//!    }
+//!    fn available_themes(&mut self, themes: AvailableThemes) -> ServerResult<()> {
+//!        Box::new(future::ok(()))
+//!    }
+//!    fn find_status(&mut self, status: FindStatusChanged) -> ServerResult<()> {
+//!        Box::new(future::ok(()))
+//!    }
+//!    fn replace_status(&mut self, status: ReplaceStatusChanged) -> ServerResult<()> {
+//!        Box::new(future::ok(()))
+//!    }
+//!    fn language_changed(&mut self, language: LanguageChanged) -> ServerResult<()> {
+//!        Box::new(future::ok(()))
+//!    }
+//!    fn alert(&mut self, alert: Alert) -> ServerResult<()> {
+//!        Box::new(future::ok(()))
+//!    }
 //! }
 //!
 //! struct MyFrontendBuilder;
@@ -115,6 +136,8 @@ extern crate tokio;
 extern crate tokio_process;
 extern crate tokio_codec;
 extern crate syntect;
+#[cfg(feature = "clipboard")]
+extern crate clipboard;
 
 mod protocol;
 mod client;
@@ -123,16 +146,24 @@ mod structs;
 mod frontend;
 mod core;
 mod cache;
+mod state;
+mod receiver;
 
 pub use cache::LineCache;
 pub use frontend::{Frontend, FrontendBuilder, ServerResult};
-pub use client::{Client, ClientResult};
+pub use client::{Client, ClientResult, NotifyResult, RequestResult};
 pub use errors::{ClientError, ServerError};
-pub use core::{spawn, CoreStderr};
+pub use core::{spawn, spawn_split, spawn_with_channel_config, CoreStderr};
+pub use receiver::{ClientReceiver, ClientSender, CoreNotification};
+pub use protocol::{ChannelConfig, OverflowPolicy};
 pub use structs::{
-    AvailablePlugins, PluginStarted, PluginStoped, ThemeChanged,
-    ThemeSettings,
-    UpdateCmds, ConfigChanged, ConfigChanges, ScrollTo, Position,
-    Update, Style, Operation, OperationType, Line, StyleDef,
-    ViewId, ModifySelection,
+    AvailablePlugins, AvailableLanguages, PluginStarted, PluginStoped, PluginStatus, ThemeChanged,
+    ThemeSettings, AvailableThemes, FindMatch, FindStatus, FindStatusChanged, ReplaceStatus,
+    ReplaceStatusChanged, LanguageChanged, Alert,
+    UpdateCmds, Config, ConfigChanged, ConfigChanges, ConfigDomain, LineEnding, ScrollTo, Position,
+    Update, Annotation, Style, StyleMap, Operation, OperationType, Line, StyleDef, StyledLine,
+    StyledSpan,
+    ViewId, ModifySelection, SelectionMode, MeasureWidth, MeasureWidthItem, SelRegion,
+    Symbol, GestureType, ClickModifiers, SelectionGranularity, ClientCapabilities, ConnectionEvent, EditEnvelope, EditMethod, EmptyParams,
+    byte_to_utf16_col, utf16_to_byte_col,
 };
@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::{Async, Future, Poll};
+use futures::task::{self, Task};
+
+/// What to do when a message is sent while the channel between the
+/// clients and the endpoint is full.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OverflowPolicy {
+    /// Queue the message anyway, so that the messages are still sent in
+    /// order, but its future only resolves once the endpoint processed
+    /// enough messages for the channel not to be full anymore.
+    Block,
+    /// Fail immediately.
+    Error,
+}
+
+/// Configuration of the channel used by the clients to send messages to the
+/// endpoint. By default, the channel is unbounded.
+///
+/// Dropping messages is deliberately not offered as a policy: losing an edit
+/// notification would make the core's buffer diverge from what the user
+/// typed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ChannelConfig {
+    /// Maximum number of messages waiting to be processed by the endpoint,
+    /// or `None` for an unbounded channel.
+    pub capacity: Option<usize>,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        ChannelConfig {
+            capacity: None,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+/// Keep track of the messages sent by the clients that have not been
+/// processed by the endpoint yet.
+pub struct Backlog {
+    config: ChannelConfig,
+    pending: AtomicUsize,
+    blocked: Mutex<Vec<Task>>,
+}
+
+impl Backlog {
+    pub fn new(config: ChannelConfig) -> Self {
+        Backlog {
+            config,
+            pending: AtomicUsize::new(0),
+            blocked: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.config.overflow
+    }
+
+    /// Count a message that is queued whether there is room or not. Return
+    /// `false` if the channel was full.
+    pub fn push(&self) -> bool {
+        let pending = self.pending.fetch_add(1, Ordering::SeqCst);
+        match self.config.capacity {
+            Some(capacity) => pending < capacity,
+            None => true,
+        }
+    }
+
+    /// Whether the channel is not over its capacity.
+    fn has_room(&self) -> bool {
+        match self.config.capacity {
+            Some(capacity) => self.pending.load(Ordering::SeqCst) <= capacity,
+            None => true,
+        }
+    }
+
+    /// Reserve room for a message. Return `false` if the channel is full.
+    pub fn reserve(&self) -> bool {
+        let pending = self.pending.fetch_add(1, Ordering::SeqCst);
+        match self.config.capacity {
+            Some(capacity) if pending >= capacity => {
+                let _ = self.pending.fetch_sub(1, Ordering::SeqCst);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Release the room of a message that has been processed, and wake up
+    /// the tasks waiting for room.
+    pub fn release(&self) {
+        let _ = self.pending.fetch_sub(1, Ordering::SeqCst);
+        for task in self.blocked.lock().unwrap().drain(..) {
+            task.notify();
+        }
+    }
+}
+
+/// A future that resolves once the backlog is not over its capacity
+/// anymore.
+pub struct Room(pub Arc<Backlog>);
+
+impl Future for Room {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.0.has_room() {
+            return Ok(Async::Ready(()));
+        }
+        self.0.blocked.lock().unwrap().push(task::current());
+        // Room may have been released before the task was registered.
+        if self.0.has_room() {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+#[test]
+fn reserve_up_to_capacity() {
+    let backlog = Backlog::new(ChannelConfig {
+        capacity: Some(2),
+        overflow: OverflowPolicy::Error,
+    });
+    assert!(backlog.reserve());
+    assert!(backlog.reserve());
+    assert!(!backlog.reserve());
+    backlog.release();
+    assert!(backlog.reserve());
+}
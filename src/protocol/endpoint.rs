@@ -2,17 +2,19 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error::Error;
 use std::io;
+use std::sync::Arc;
 
-use futures::{Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
 use futures::sync::{mpsc, oneshot};
 use tokio_codec::{Framed, Decoder};
 use tokio::io::{AsyncRead, AsyncWrite};
 use serde_json::Value;
 
-use super::message::{Message, Notification, Request};
+use super::message::{Message, Notification, Params, Request};
 use super::message::Response as ResponseMessage;
 use super::codec::Codec;
 use super::errors::RpcError;
+use super::backlog::{Backlog, ChannelConfig, OverflowPolicy, Room};
 
 pub trait Service: Send {
     type Error: Error;
@@ -95,14 +97,14 @@ impl<S: Service> Server<S> {
 
     fn process_request(&mut self, request: Request) {
         let method = request.method.as_str();
-        let params = request.params;
+        let params = request.params.into_value();
         let response = self.service.handle_request(method, params);
         self.request_tasks.insert(request.id, response);
     }
 
     fn process_notification(&mut self, notification: Notification) {
         let method = notification.method.as_str();
-        let params = notification.params;
+        let params = notification.params.into_value();
         let task = self.service.handle_notification(method, params);
         self.notification_tasks.push(task);
     }
@@ -110,29 +112,41 @@ impl<S: Service> Server<S> {
 
 type ResponseTx = oneshot::Sender<Result<Value, Value>>;
 /// Future response to a request. It resolved once the response is available.
-pub struct Response(oneshot::Receiver<Result<Value, Value>>);
+pub struct Response(Box<Future<Item = Result<Value, Value>, Error = RpcError> + Send + Sync>);
 
 type AckTx = oneshot::Sender<()>;
 
 /// A future that resolves when a notification has been effictively sent to the
 /// server. It does not guarantees that the server receives it, just that it
 /// has been sent.
-pub struct Ack(oneshot::Receiver<()>);
+pub struct Ack(Box<Future<Item = (), Error = RpcError> + Send + Sync>);
+
+/// A message sent by a client to the endpoint. Requests and notifications
+/// go through the same channel, so that they are sent to the remote peer in
+/// the order they were sent by the clients.
+enum Outgoing {
+    Request(Request, ResponseTx),
+    Notification(Notification, AckTx),
+}
+
+type OutgoingTx = mpsc::UnboundedSender<Outgoing>;
+type OutgoingRx = mpsc::UnboundedReceiver<Outgoing>;
+
+type ClosedTx = oneshot::Sender<()>;
 
-type RequestTx = mpsc::UnboundedSender<(Request, ResponseTx)>;
-type RequestRx = mpsc::UnboundedReceiver<(Request, ResponseTx)>;
+/// A future that resolves once the connection with the remote peer has been
+/// closed, after a shutdown was requested.
+pub struct Closed(oneshot::Receiver<()>);
 
-type NotificationTx = mpsc::UnboundedSender<(Notification, AckTx)>;
-type NotificationRx = mpsc::UnboundedReceiver<(Notification, AckTx)>;
+type ShutdownTx = mpsc::UnboundedSender<ClosedTx>;
+type ShutdownRx = mpsc::UnboundedReceiver<ClosedTx>;
 
 impl Future for Response {
     type Item = Result<Value, Value>;
     type Error = RpcError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.0
-            .poll()
-            .map_err(|oneshot::Canceled| RpcError::ResponseCanceled)
+        self.0.poll()
     }
 }
 
@@ -140,36 +154,50 @@ impl Future for Ack {
     type Item = ();
     type Error = RpcError;
 
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+impl Future for Closed {
+    type Item = ();
+    type Error = RpcError;
+
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         self.0
             .poll()
-            .map_err(|oneshot::Canceled| RpcError::AckCanceled)
+            .map_err(|oneshot::Canceled| RpcError::ShutdownInterrupted)
     }
 }
 
 struct InnerClient {
     shutting_down: bool,
     request_id: u64,
-    requests_rx: RequestRx,
-    notifications_rx: NotificationRx,
+    outgoing_rx: OutgoingRx,
+    shutdown_rx: ShutdownRx,
     pending_requests: HashMap<u64, ResponseTx>,
     pending_notifications: Vec<AckTx>,
+    closed_waiters: Vec<ClosedTx>,
+    backlog: Arc<Backlog>,
 }
 
 impl InnerClient {
-    fn new() -> (Self, Client) {
-        let (requests_tx, requests_rx) = mpsc::unbounded();
-        let (notifications_tx, notifications_rx) = mpsc::unbounded();
+    fn new(config: ChannelConfig) -> (Self, Client) {
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded();
+        let (shutdown_tx, shutdown_rx) = mpsc::unbounded();
+        let backlog = Arc::new(Backlog::new(config));
 
-        let client_proxy = Client::new(requests_tx, notifications_tx);
+        let client_proxy = Client::new(outgoing_tx, shutdown_tx, backlog.clone());
 
         let client = InnerClient {
             shutting_down: false,
             request_id: 0,
-            requests_rx,
-            notifications_rx,
+            outgoing_rx,
+            shutdown_rx,
             pending_requests: HashMap::new(),
             pending_notifications: Vec::new(),
+            closed_waiters: Vec::new(),
+            backlog,
         };
 
         (client, client_proxy)
@@ -184,58 +212,57 @@ impl InnerClient {
         self.shutting_down
     }
 
-    fn process_notifications<T: AsyncRead + AsyncWrite>(&mut self, stream: &mut Transport<T>) {
-        trace!("polling client notifications channel");
+    fn process_messages<T: AsyncRead + AsyncWrite>(&mut self, stream: &mut Transport<T>) {
+        trace!("polling client messages channel");
         loop {
-            match self.notifications_rx.poll() {
-                Ok(Async::Ready(Some((notification, ack_sender)))) => {
+            match self.outgoing_rx.poll() {
+                Ok(Async::Ready(Some(Outgoing::Request(mut request, response_sender)))) => {
+                    self.backlog.release();
+                    self.request_id += 1;
+                    trace!("sending request: {:?}", request);
+                    request.id = self.request_id;
+                    stream.send(Message::Request(request));
+                    self.pending_requests
+                        .insert(self.request_id, response_sender);
+                }
+                Ok(Async::Ready(Some(Outgoing::Notification(notification, ack_sender)))) => {
+                    self.backlog.release();
                     trace!("sending notification: {:?}", notification);
                     stream.send(Message::Notification(notification));
                     self.pending_notifications.push(ack_sender);
                 }
                 Ok(Async::NotReady) => {
-                    trace!("no new notification from client");
+                    trace!("no new message from client");
                     break;
                 }
                 Ok(Async::Ready(None)) => {
-                    warn!("client closed the notifications channel");
+                    // All the messages queued before the client was dropped
+                    // have been sent at this point.
+                    warn!("client closed the messages channel");
                     self.shutdown();
+                    break;
                 }
                 Err(()) => {
                     // I have no idea how this should be handled.
                     // The documentation does not tell what may trigger an error.
-                    error!("an error occured while polling the notifications channel");
-                    panic!("an error occured while polling the notifications channel");
+                    error!("an error occured while polling the messages channel");
+                    panic!("an error occured while polling the messages channel");
                 }
             }
         }
     }
 
-    fn process_requests<T: AsyncRead + AsyncWrite>(&mut self, stream: &mut Transport<T>) {
-        trace!("polling client requests channel");
+    fn process_shutdown_requests(&mut self) {
+        trace!("polling client shutdown channel");
         loop {
-            match self.requests_rx.poll() {
-                Ok(Async::Ready(Some((mut request, response_sender)))) => {
-                    self.request_id += 1;
-                    trace!("sending request: {:?}", request);
-                    request.id = self.request_id;
-                    stream.send(Message::Request(request));
-                    self.pending_requests
-                        .insert(self.request_id, response_sender);
-                }
-                Ok(Async::Ready(None)) => {
-                    warn!("client closed the requests channel.");
+            match self.shutdown_rx.poll() {
+                Ok(Async::Ready(Some(closed_sender))) => {
+                    debug!("client requested a shutdown");
+                    self.closed_waiters.push(closed_sender);
                     self.shutdown();
                 }
-                Ok(Async::NotReady) => {
-                    trace!("no new request from client");
-                    break;
-                }
-                Err(()) => {
-                    // I have no idea how this should be handled.
-                    // The documentation does not tell what may trigger an error.
-                    panic!("An error occured while polling the requests channel");
-                }
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => break,
+                Err(()) => panic!("An error occured while polling the shutdown channel"),
             }
         }
     }
@@ -268,6 +295,12 @@ pub struct Endpoint<S: Service, T: AsyncRead + AsyncWrite> {
     stream: RefCell<Transport<T>>,
     client: Option<RefCell<InnerClient>>,
     server: Option<RefCell<Server<S>>>,
+    /// Set when the client requested a shutdown: the stream must be closed.
+    closing: bool,
+    closed_waiters: Vec<ClosedTx>,
+    /// Acknowledgements of the notifications sent by a client that has
+    /// shut down, to send once the stream is flushed.
+    pending_acks: Vec<AckTx>,
 }
 
 struct Transport<T: AsyncRead + AsyncWrite>(Framed<T, Codec>);
@@ -313,6 +346,10 @@ where
     fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
         self.0.poll_complete()
     }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        self.0.close()
+    }
 }
 
 impl<S, T> Endpoint<S, T>
@@ -325,6 +362,9 @@ where
             stream: RefCell::new(Transport(Codec.framed(stream))),
             client: None,
             server: None,
+            closing: false,
+            closed_waiters: Vec::new(),
+            pending_acks: Vec::new(),
         }
     }
 
@@ -332,8 +372,8 @@ where
         self.server = Some(RefCell::new(Server::new(service)));
     }
 
-    pub fn set_client(&mut self) -> Client {
-        let (client, client_proxy) = InnerClient::new();
+    pub fn set_client(&mut self, config: ChannelConfig) -> Client {
+        let (client, client_proxy) = InnerClient::new(config);
         self.client = Some(RefCell::new(client));
         client_proxy
     }
@@ -371,13 +411,33 @@ where
     fn flush(&mut self) {
         trace!("flushing stream");
         match self.stream.get_mut().poll_complete() {
-            Ok(Async::Ready(())) => if let Some(ref mut client) = self.client {
-                client.get_mut().acknowledge_notifications();
-            },
+            Ok(Async::Ready(())) => {
+                if let Some(ref mut client) = self.client {
+                    client.get_mut().acknowledge_notifications();
+                }
+                for ack in self.pending_acks.drain(..) {
+                    let _ = ack.send(());
+                }
+            }
             Ok(Async::NotReady) => return,
             Err(e) => panic!("Failed to flush the sink: {:?}", e),
         }
     }
+
+    fn close(&mut self) {
+        trace!("closing stream");
+        match self.stream.get_mut().close() {
+            Ok(Async::Ready(())) => debug!("stream closed, waiting for the remote peer to exit"),
+            Ok(Async::NotReady) => trace!("stream not closed yet"),
+            Err(e) => warn!("Failed to close the stream: {:?}", e),
+        }
+    }
+
+    fn notify_closed(&mut self) {
+        for chan in self.closed_waiters.drain(..) {
+            let _ = chan.send(());
+        }
+    }
 }
 
 impl<S, T: AsyncRead + AsyncWrite> Future for Endpoint<S, T>
@@ -394,6 +454,7 @@ where
                 Async::Ready(Some(msg)) => self.handle_message(msg),
                 Async::Ready(None) => {
                     warn!("stream closed by remote peer.");
+                    self.notify_closed();
                     return Ok(Async::Ready(()));
                 }
                 Async::NotReady => {
@@ -413,18 +474,28 @@ where
         if let Some(ref mut client) = self.client {
             let client = client.get_mut();
             let stream = self.stream.get_mut();
-            client.process_requests(stream);
-            client.process_notifications(stream);
+            client.process_messages(stream);
+            client.process_shutdown_requests();
             if client.is_shutting_down() {
                 warn!("Client shut down, exiting");
                 client_shutdown = true;
             }
         }
         if client_shutdown {
-            self.client = None;
+            if let Some(client) = self.client.take() {
+                let client = client.into_inner();
+                // The notifications sent before the shutdown are still in
+                // the stream's buffer, and are acknowledged once flushed.
+                self.pending_acks.extend(client.pending_notifications);
+                self.closing = self.closing || !client.closed_waiters.is_empty();
+                self.closed_waiters.extend(client.closed_waiters);
+            }
         }
 
         self.flush();
+        if self.closing {
+            self.close();
+        }
         Ok(Async::NotReady)
     }
 }
@@ -438,19 +509,57 @@ pub trait ServiceBuilder {
 
 #[derive(Clone)]
 pub struct Client {
-    requests_tx: RequestTx,
-    notifications_tx: NotificationTx,
+    outgoing_tx: OutgoingTx,
+    shutdown_tx: ShutdownTx,
+    backlog: Arc<Backlog>,
 }
 
 impl Client {
-    fn new(requests_tx: RequestTx, notifications_tx: NotificationTx) -> Self {
+    fn new(outgoing_tx: OutgoingTx, shutdown_tx: ShutdownTx, backlog: Arc<Backlog>) -> Self {
         Client {
-            requests_tx,
-            notifications_tx,
+            outgoing_tx,
+            shutdown_tx,
+            backlog,
         }
     }
 
-    pub fn request(&self, method: &str, params: Value) -> Response {
+    /// Run `send` immediately if there is room in the backlog. Otherwise,
+    /// depending on the overflow policy, fail without sending anything, or
+    /// run it anyway so that the messages keep their order, and only
+    /// resolve once room has been made. The returned future resolves with
+    /// the result of `rx`.
+    fn send_or_wait<F, R>(&self, send: F, rx: R) -> Box<Future<Item = R::Item, Error = RpcError> + Send + Sync>
+    where
+        F: FnOnce() + Send + Sync + 'static,
+        R: Future<Error = RpcError> + Send + Sync + 'static,
+        R::Item: Send + Sync,
+    {
+        match self.backlog.overflow_policy() {
+            OverflowPolicy::Error => {
+                if !self.backlog.reserve() {
+                    return Box::new(future::err(RpcError::ChannelFull));
+                }
+                send();
+                Box::new(rx)
+            }
+            OverflowPolicy::Block => {
+                let has_room = self.backlog.push();
+                send();
+                if has_room {
+                    return Box::new(rx);
+                }
+                let room = Room(self.backlog.clone()).map_err(|()| RpcError::ChannelFull);
+                Box::new(room.and_then(|()| rx))
+            }
+        }
+    }
+
+    /// Send a request. Each request gets a unique id, and the response of
+    /// the remote peer is matched to its request by this id, so several
+    /// requests can be in flight at the same time, and their responses can
+    /// arrive in any order.
+    pub fn request<P: Into<Params>>(&self, method: &str, params: P) -> Response {
+        let params = params.into();
         trace!(
             "forwarding request to endpoint (method={}, params={:?})",
             method,
@@ -462,15 +571,21 @@ impl Client {
             params,
         };
         let (tx, rx) = oneshot::channel();
-        // If send returns an Err, its because the other side has been dropped.
-        // By ignoring it, we are just dropping the `tx`, which will mean the
-        // rx will return Canceled when polled. In turn, that is translated
-        // into a BrokenPipe, which conveys the proper error.
-        let _ = mpsc::UnboundedSender::unbounded_send(&self.requests_tx, (request, tx));
-        Response(rx)
+        let outgoing_tx = self.outgoing_tx.clone();
+        let send = move || {
+            // If send returns an Err, its because the other side has been
+            // dropped. By ignoring it, we are just dropping the `tx`, which
+            // will mean the rx will return Canceled when polled. In turn,
+            // that is translated into a BrokenPipe, which conveys the proper
+            // error.
+            let _ = mpsc::UnboundedSender::unbounded_send(&outgoing_tx, Outgoing::Request(request, tx));
+        };
+        let rx = rx.map_err(|oneshot::Canceled| RpcError::ResponseCanceled);
+        Response(self.send_or_wait(send, rx))
     }
 
-    pub fn notify(&self, method: &str, params: Value) -> Ack {
+    pub fn notify<P: Into<Params>>(&self, method: &str, params: P) -> Ack {
+        let params = params.into();
         trace!(
             "forwarding notification to endpoint (method={}, params={:?})",
             method,
@@ -481,8 +596,22 @@ impl Client {
             params,
         };
         let (tx, rx) = oneshot::channel();
-        let _ = mpsc::UnboundedSender::unbounded_send(&self.notifications_tx, (notification, tx));
-        Ack(rx)
+        let outgoing_tx = self.outgoing_tx.clone();
+        let send = move || {
+            let message = Outgoing::Notification(notification, tx);
+            let _ = mpsc::UnboundedSender::unbounded_send(&outgoing_tx, message);
+        };
+        let rx = rx.map_err(|oneshot::Canceled| RpcError::AckCanceled);
+        Ack(self.send_or_wait(send, rx))
+    }
+
+    /// Ask the endpoint to close the connection, once the pending messages
+    /// have been sent.
+    pub fn shutdown(&self) -> Closed {
+        trace!("forwarding shutdown request to endpoint");
+        let (tx, rx) = oneshot::channel();
+        let _ = mpsc::UnboundedSender::unbounded_send(&self.shutdown_tx, tx);
+        Closed(rx)
     }
 }
 
@@ -494,3 +623,131 @@ impl Future for Client {
         Ok(Async::Ready(()))
     }
 }
+
+/// Records what is written on the wire for the messages sent with a
+/// `Client`, instead of sending them to a remote peer.
+#[cfg(test)]
+pub struct Recorder {
+    inner: InnerClient,
+    stream: Transport<io::Cursor<Vec<u8>>>,
+}
+
+#[cfg(test)]
+impl Recorder {
+    pub fn new() -> (Self, Client) {
+        let (inner, client) = InnerClient::new(ChannelConfig::default());
+        let stream = Transport(Codec.framed(io::Cursor::new(Vec::new())));
+        (Recorder { inner, stream }, client)
+    }
+
+    /// Return the messages sent since the last call, as written on the
+    /// wire. The notifications are then acknowledged.
+    pub fn messages(&mut self) -> Vec<Value> {
+        let Recorder { ref mut inner, ref mut stream } = *self;
+        future::lazy(|| {
+            inner.process_messages(stream);
+            stream.poll_complete()
+        }).wait()
+            .unwrap();
+        inner.acknowledge_notifications();
+        let cursor = stream.0.get_mut();
+        cursor.set_position(0);
+        let written = ::std::mem::replace(cursor.get_mut(), Vec::new());
+        String::from_utf8(written)
+            .unwrap()
+            .lines()
+            .map(|line| ::serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// Answer a request returned by `messages` with the given result.
+    pub fn respond(&mut self, id: u64, result: Value) {
+        if let Some(tx) = self.inner.pending_requests.remove(&id) {
+            let _ = tx.send(Ok(result));
+        }
+    }
+}
+
+#[test]
+fn responses_are_matched_by_id() {
+    use std::io::Cursor;
+
+    let (mut inner, client) = InnerClient::new(ChannelConfig::default());
+    let mut stream = Transport(Codec.framed(Cursor::new(Vec::new())));
+    let first = client.request("copy", json!({"view_id": "view-id-1"}));
+    let second = client.request("copy", json!({"view_id": "view-id-2"}));
+    // Polling the channels must be done from a task.
+    future::lazy(|| {
+        inner.process_messages(&mut stream);
+        // The responses arrive in the opposite order.
+        inner.process_response(ResponseMessage { id: 2, result: Ok(json!("second")) });
+        inner.process_response(ResponseMessage { id: 1, result: Ok(json!("first")) });
+        Ok::<(), ()>(())
+    }).wait()
+        .unwrap();
+    assert_eq!(first.wait().ok(), Some(Ok(json!("first"))));
+    assert_eq!(second.wait().ok(), Some(Ok(json!("second"))));
+}
+
+#[test]
+fn notifications_are_sent_after_client_is_dropped() {
+    use std::io::Cursor;
+
+    let (mut inner, client) = InnerClient::new(ChannelConfig::default());
+    let mut stream = Transport(Codec.framed(Cursor::new(Vec::new())));
+    let _ = client.notify("save", json!({"view_id": "view-id-1"}));
+    drop(client);
+    future::lazy(|| {
+        inner.process_messages(&mut stream);
+        stream.poll_complete()
+    }).wait()
+        .unwrap();
+    assert!(inner.is_shutting_down());
+    let written = String::from_utf8(stream.0.get_ref().get_ref().clone()).unwrap();
+    assert!(written.contains("\"save\""));
+}
+
+#[test]
+fn messages_are_sent_in_order() {
+    use std::io::Cursor;
+
+    let (mut inner, client) = InnerClient::new(ChannelConfig::default());
+    let mut stream = Transport(Codec.framed(Cursor::new(Vec::new())));
+    let _ = client.notify("insert", json!({}));
+    let _ = client.request("copy", json!({}));
+    let _ = client.notify("save", json!({}));
+    future::lazy(|| {
+        inner.process_messages(&mut stream);
+        stream.poll_complete()
+    }).wait()
+        .unwrap();
+    let written = String::from_utf8(stream.0.get_ref().get_ref().clone()).unwrap();
+    let insert = written.find("\"insert\"").unwrap();
+    let copy = written.find("\"copy\"").unwrap();
+    let save = written.find("\"save\"").unwrap();
+    assert!(insert < copy && copy < save);
+}
+
+#[test]
+fn blocked_messages_are_sent_in_order() {
+    use std::io::Cursor;
+
+    let config = ChannelConfig { capacity: Some(1), overflow: OverflowPolicy::Block };
+    let (mut inner, client) = InnerClient::new(config);
+    let mut stream = Transport(Codec.framed(Cursor::new(Vec::new())));
+    let _ = client.notify("insert", json!({}));
+    // The channel is full: the following messages are not polled, but
+    // must still be written after the first one, in order.
+    let _ = client.notify("delete_backward", json!({}));
+    let _ = client.request("copy", json!({}));
+    future::lazy(|| {
+        inner.process_messages(&mut stream);
+        stream.poll_complete()
+    }).wait()
+        .unwrap();
+    let written = String::from_utf8(stream.0.get_ref().get_ref().clone()).unwrap();
+    let insert = written.find("\"insert\"").unwrap();
+    let delete = written.find("\"delete_backward\"").unwrap();
+    let copy = written.find("\"copy\"").unwrap();
+    assert!(insert < delete && delete < copy);
+}
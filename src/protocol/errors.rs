@@ -52,4 +52,6 @@ impl From<SerdeError> for DecodeError {
 pub enum RpcError {
     ResponseCanceled,
     AckCanceled,
+    ShutdownInterrupted,
+    ChannelFull,
 }
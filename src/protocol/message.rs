@@ -1,5 +1,7 @@
 use std::io::Read;
-use serde_json::{from_reader, to_vec, Value};
+use serde::{Serialize, Serializer};
+use serde_json::{from_reader, from_str, to_vec, Value};
+use serde_json::value::RawValue;
 
 use super::errors::*;
 
@@ -10,11 +12,70 @@ pub enum Message {
     Notification(Notification),
 }
 
+/// The parameters of a request or a notification. They are usually a
+/// `Value`, but parameters that are already serialized can be sent as a
+/// `RawValue`, which is written as is.
+#[derive(Clone, Debug)]
+pub enum Params {
+    Value(Value),
+    Raw(Box<RawValue>),
+}
+
+impl Params {
+    /// Convert the parameters into a `Value`. Raw parameters are parsed,
+    /// and are replaced by `null` if they are not valid JSON.
+    pub fn into_value(self) -> Value {
+        match self {
+            Params::Value(value) => value,
+            Params::Raw(raw) => from_str(raw.get()).unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl From<Value> for Params {
+    fn from(value: Value) -> Self {
+        Params::Value(value)
+    }
+}
+
+impl From<Box<RawValue>> for Params {
+    fn from(raw: Box<RawValue>) -> Self {
+        Params::Raw(raw)
+    }
+}
+
+impl PartialEq for Params {
+    fn eq(&self, other: &Params) -> bool {
+        match *self {
+            Params::Value(ref a) => match *other {
+                Params::Value(ref b) => a == b,
+                Params::Raw(_) => false,
+            },
+            Params::Raw(ref a) => match *other {
+                Params::Raw(ref b) => a.get() == b.get(),
+                Params::Value(_) => false,
+            },
+        }
+    }
+}
+
+impl Serialize for Params {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Params::Value(ref value) => value.serialize(serializer),
+            Params::Raw(ref raw) => raw.serialize(serializer),
+        }
+    }
+}
+
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct Request {
     pub id: u64,
     pub method: String,
-    pub params: Value,
+    pub params: Params,
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug)]
@@ -26,7 +87,7 @@ pub struct Response {
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct Notification {
     pub method: String,
-    pub params: Value,
+    pub params: Params,
 }
 
 impl Message {
@@ -78,7 +139,7 @@ impl Notification {
 
         Ok(Notification {
             method,
-            params,
+            params: Params::Value(params),
         })
     }
 }
@@ -104,7 +165,7 @@ impl Request {
         Ok(Request {
             id,
             method,
-            params,
+            params: Params::Value(params),
         })
     }
 }
@@ -160,3 +221,16 @@ fn get_message_type(value: &Value) -> ValueType {
         ValueType::Invalid
     }
 }
+
+#[test]
+fn serialize_raw_params() {
+    let raw = RawValue::from_string(r#"{"chars": "a"}"#.to_string()).unwrap();
+    let notification = Message::Notification(Notification {
+        method: "insert".into(),
+        params: raw.into(),
+    });
+    assert_eq!(
+        String::from_utf8(notification.to_vec()).unwrap(),
+        r#"{"method":"insert","params":{"chars": "a"}}"#
+    );
+}
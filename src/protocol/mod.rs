@@ -2,5 +2,9 @@ pub mod errors;
 pub mod codec;
 pub mod message;
 pub mod endpoint;
+pub mod backlog;
 
 pub use self::endpoint::{Ack, Client, Endpoint, Response, Service, ServiceBuilder};
+pub use self::backlog::{ChannelConfig, OverflowPolicy};
+#[cfg(test)]
+pub use self::endpoint::Recorder;
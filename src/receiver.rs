@@ -0,0 +1,132 @@
+use futures::{future, Poll, Stream};
+use futures::sync::mpsc;
+
+use client::Client;
+use errors::ServerError;
+use frontend::{Frontend, FrontendBuilder, ServerResult};
+use structs::{
+    AvailableLanguages, AvailablePlugins, AvailableThemes, FindStatusChanged,
+    ReplaceStatusChanged, LanguageChanged, Alert, ConfigChanged, MeasureWidth,
+    PluginStarted, PluginStatus, PluginStoped, ScrollTo, Style, ThemeChanged, Update,
+    UpdateCmds,
+};
+
+/// The sending half of a connection to `xi-core`. It is cheap to clone, and
+/// all the clones share the same connection.
+pub type ClientSender = Client;
+
+/// A notification sent by `xi-core`, as yielded by a `ClientReceiver`.
+#[derive(Debug)]
+pub enum CoreNotification {
+    Update(Update),
+    ScrollTo(ScrollTo),
+    DefStyle(Style),
+    AvailablePlugins(AvailablePlugins),
+    UpdateCmds(UpdateCmds),
+    /// A plugin has been started or stopped for a view.
+    PluginStatus(PluginStatus),
+    ConfigChanged(ConfigChanged),
+    ThemeChanged(ThemeChanged),
+    AvailableLanguages(AvailableLanguages),
+    AvailableThemes(AvailableThemes),
+    FindStatus(FindStatusChanged),
+    ReplaceStatus(ReplaceStatusChanged),
+    LanguageChanged(LanguageChanged),
+    Alert(Alert),
+}
+
+/// The receiving half of a connection to `xi-core`: a stream of the
+/// notifications it sends. Unlike the `ClientSender`, it cannot be cloned,
+/// so there is a single owner for the notifications. It is `Send` and
+/// `Sync`, so it can be moved to the thread that consumes them.
+pub struct ClientReceiver(mpsc::UnboundedReceiver<CoreNotification>);
+
+impl Stream for ClientReceiver {
+    type Item = CoreNotification;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.0.poll()
+    }
+}
+
+/// A `Frontend` that forwards the notifications to a `ClientReceiver`.
+pub struct ChannelFrontend(mpsc::UnboundedSender<CoreNotification>);
+
+impl ChannelFrontend {
+    fn forward(&mut self, notification: CoreNotification) -> ServerResult<()> {
+        match self.0.unbounded_send(notification) {
+            Ok(()) => Box::new(future::ok(())),
+            Err(_) => Box::new(future::err(ServerError::from("the ClientReceiver was dropped"))),
+        }
+    }
+}
+
+impl Frontend for ChannelFrontend {
+    fn update(&mut self, update: Update) -> ServerResult<()> {
+        self.forward(CoreNotification::Update(update))
+    }
+    fn scroll_to(&mut self, scroll_to: ScrollTo) -> ServerResult<()> {
+        self.forward(CoreNotification::ScrollTo(scroll_to))
+    }
+    fn def_style(&mut self, style: Style) -> ServerResult<()> {
+        self.forward(CoreNotification::DefStyle(style))
+    }
+    fn available_plugins(&mut self, plugins: AvailablePlugins) -> ServerResult<()> {
+        self.forward(CoreNotification::AvailablePlugins(plugins))
+    }
+    fn update_cmds(&mut self, cmds: UpdateCmds) -> ServerResult<()> {
+        self.forward(CoreNotification::UpdateCmds(cmds))
+    }
+    fn plugin_started(&mut self, plugin: PluginStarted) -> ServerResult<()> {
+        self.forward(CoreNotification::PluginStatus(plugin.into()))
+    }
+    fn plugin_stoped(&mut self, plugin: PluginStoped) -> ServerResult<()> {
+        self.forward(CoreNotification::PluginStatus(plugin.into()))
+    }
+    fn config_changed(&mut self, config: ConfigChanged) -> ServerResult<()> {
+        self.forward(CoreNotification::ConfigChanged(config))
+    }
+    fn theme_changed(&mut self, theme: ThemeChanged) -> ServerResult<()> {
+        self.forward(CoreNotification::ThemeChanged(theme))
+    }
+    fn measure_width(&mut self, _request: MeasureWidth) -> ServerResult<Vec<Vec<f32>>> {
+        // A stream of notifications has no way to answer a request.
+        Box::new(future::err(ServerError::from(
+            "measure_width is not supported with a ClientReceiver",
+        )))
+    }
+    fn available_languages(&mut self, languages: AvailableLanguages) -> ServerResult<()> {
+        self.forward(CoreNotification::AvailableLanguages(languages))
+    }
+    fn available_themes(&mut self, themes: AvailableThemes) -> ServerResult<()> {
+        self.forward(CoreNotification::AvailableThemes(themes))
+    }
+    fn find_status(&mut self, status: FindStatusChanged) -> ServerResult<()> {
+        self.forward(CoreNotification::FindStatus(status))
+    }
+    fn replace_status(&mut self, status: ReplaceStatusChanged) -> ServerResult<()> {
+        self.forward(CoreNotification::ReplaceStatus(status))
+    }
+    fn language_changed(&mut self, language: LanguageChanged) -> ServerResult<()> {
+        self.forward(CoreNotification::LanguageChanged(language))
+    }
+    fn alert(&mut self, alert: Alert) -> ServerResult<()> {
+        self.forward(CoreNotification::Alert(alert))
+    }
+}
+
+pub struct ChannelFrontendBuilder(mpsc::UnboundedSender<CoreNotification>);
+
+impl FrontendBuilder<ChannelFrontend> for ChannelFrontendBuilder {
+    fn build(self, _client: Client) -> ChannelFrontend {
+        ChannelFrontend(self.0)
+    }
+}
+
+/// Create the builder of a `Frontend` that forwards all the notifications
+/// to the returned `ClientReceiver`.
+pub fn channel() -> (ChannelFrontendBuilder, ClientReceiver) {
+    let (tx, rx) = mpsc::unbounded();
+    (ChannelFrontendBuilder(tx), ClientReceiver(rx))
+}
@@ -0,0 +1,372 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use futures::sync::{mpsc, oneshot};
+use serde_json::Value;
+
+use cache::LineCache;
+use structs::{AvailableLanguages, AvailableThemes, ConfigChanged, ConnectionEvent, EditEnvelope, EmptyParams, FindStatusChanged, ThemeChanged, ThemeSettings, LanguageChanged, LineEnding, PluginStatus, SelectionGranularity, SelectionMode, SelRegion, Style, StyleMap, Update, ViewId};
+
+/// State shared between all the clones of a `Client` and the service that
+/// dispatches the core's messages to the `Frontend`.
+pub type SharedState = Arc<Mutex<ClientState>>;
+
+/// A function answering the requests of a given method that the core
+/// forwards to the frontend on behalf of plugins.
+pub struct RequestHandler(pub Box<FnMut(Value) -> Result<Value, Value> + Send>);
+
+impl fmt::Debug for RequestHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RequestHandler")
+    }
+}
+
+/// The parameters of a `"find"` command.
+#[derive(Debug, Clone)]
+pub struct FindQuery {
+    pub chars: String,
+    pub case_sensitive: bool,
+    pub regex: bool,
+    pub whole_words: bool,
+}
+
+/// What the client knows about a view, built from the notifications sent by
+/// the core.
+#[derive(Debug, Default)]
+pub struct ViewState {
+    pub cache: LineCache,
+    pub update_waiters: Vec<oneshot::Sender<Update>>,
+    /// Plugins that have been started for this view.
+    pub plugins: HashSet<String>,
+    /// Whether the view had no unsaved changes in the last update, if an
+    /// update has been received.
+    pub pristine: Option<bool>,
+    /// Streams returned by `Client::pristine_stream`.
+    pub pristine_subscribers: Vec<mpsc::UnboundedSender<bool>>,
+    /// Range given to the last `scroll_throttled`, if it has not been sent
+    /// yet.
+    pub pending_scroll: Option<(u64, u64)>,
+    /// Range sent by the last `scroll_fractional`, and the difference
+    /// between the fractional first line it was given and the first line
+    /// of that range.
+    pub fractional_scroll: Option<((u64, u64), f64)>,
+    /// Line ending of the view, from the last `"config_changed"`
+    /// notification that set it.
+    pub line_ending: Option<LineEnding>,
+    /// File the view was opened with or last saved to.
+    pub file_path: Option<String>,
+    /// Language of the view, from the last `"language_changed"`
+    /// notification.
+    pub language: Option<String>,
+    /// Futures waiting for the `"language_changed"` notification of a
+    /// language.
+    pub language_waiters: Vec<(String, oneshot::Sender<()>)>,
+    /// Granularity applied to the `drag` gestures of the view.
+    pub granularity: SelectionGranularity,
+    /// The last `find` issued for the view.
+    pub last_find: Option<FindQuery>,
+    /// Selections of the view when the current search began, restored by
+    /// `Client::cancel_find`.
+    pub find_origin: Option<Vec<SelRegion>>,
+    /// Streams returned by `Client::find_all_paged`.
+    pub find_status_subscribers: Vec<mpsc::UnboundedSender<FindStatusChanged>>,
+    /// Incremented by each `find_all_cancelable` and `cancel_find_all`,
+    /// so that a pending search can tell it has been superseded.
+    pub find_generation: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ClientState {
+    pub views: HashMap<ViewId, ViewState>,
+    pub styles: StyleMap,
+    /// Languages announced by the last `"available_languages"`
+    /// notification.
+    pub languages: Option<Vec<String>>,
+    pub languages_waiters: Vec<oneshot::Sender<Vec<String>>>,
+    /// Themes announced by the last `"available_themes"` notification.
+    pub themes: Option<Vec<String>>,
+    pub themes_subscribers: Vec<mpsc::UnboundedSender<Vec<String>>>,
+    /// Futures waiting for the `"theme_changed"` notification of a theme.
+    pub theme_waiters: Vec<(String, oneshot::Sender<ThemeChanged>)>,
+    /// Name of the theme announced by the last `"theme_changed"`
+    /// notification.
+    pub theme: Option<String>,
+    /// Colors of the themes announced by `"theme_changed"` notifications,
+    /// indexed by name.
+    pub theme_settings: HashMap<String, ThemeSettings>,
+    /// Streams returned by `plugin_notifications`, with the name of the
+    /// plugin they are interested in.
+    pub plugin_subscribers: Vec<(String, mpsc::UnboundedSender<(String, Value)>)>,
+    /// How the `"edit"` commands are wrapped.
+    pub edit_envelope: EditEnvelope,
+    /// What is sent for the edit commands given no parameters.
+    pub empty_params: EmptyParams,
+    /// How the `*_sel` movements modify the selections by default.
+    pub selection_mode: SelectionMode,
+    /// Handlers registered with `Client::register_request_handler`, by
+    /// method.
+    pub request_handlers: HashMap<String, RequestHandler>,
+    /// Text of the last `copy` or `cut`, with the text of the following
+    /// `copy_append` and `cut_append` appended to it.
+    pub kill_buffer: String,
+    /// View given by the last `focus_view`, unless it has been blurred.
+    pub focused_view: Option<ViewId>,
+    /// Views opened with `new_view` and not closed yet.
+    pub open_views: HashSet<ViewId>,
+    /// The last change of the state of the connection with the core.
+    pub connection: Option<ConnectionEvent>,
+    /// Streams returned by `Client::connection_events`.
+    pub connection_subscribers: Vec<mpsc::UnboundedSender<ConnectionEvent>>,
+    /// Whether messages for views that are not open should be rejected.
+    pub strict: bool,
+}
+
+impl ClientState {
+    pub fn view_mut(&mut self, view_id: ViewId) -> &mut ViewState {
+        self.views.entry(view_id).or_default()
+    }
+
+    /// Record an `"update"` notification, and wake up the futures waiting
+    /// for it. If the view went from pristine to dirty or back, the new
+    /// state is sent to the streams returned by `Client::pristine_stream`.
+    pub fn handle_update(&mut self, update: &Update) {
+        let view = self.view_mut(update.view_id);
+        view.cache.update(update.clone());
+        if view.pristine != Some(update.pristine) {
+            view.pristine_subscribers
+                .retain(|subscriber| subscriber.unbounded_send(update.pristine).is_ok());
+        }
+        view.pristine = Some(update.pristine);
+        for waiter in view.update_waiters.drain(..) {
+            let _ = waiter.send(update.clone());
+        }
+    }
+
+    /// Record a change of the state of the connection with the core, and
+    /// send it to the streams returned by `Client::connection_events`.
+    pub fn handle_connection_event(&mut self, event: ConnectionEvent) {
+        self.connection_subscribers
+            .retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+        self.connection = Some(event);
+    }
+
+    /// Record the languages announced by the core, and wake up the futures
+    /// waiting for them.
+    pub fn handle_available_languages(&mut self, languages: &AvailableLanguages) {
+        self.languages = Some(languages.languages.clone());
+        for waiter in self.languages_waiters.drain(..) {
+            let _ = waiter.send(languages.languages.clone());
+        }
+    }
+
+    /// Record the themes announced by the core, and send them to the
+    /// streams returned by `Client::theme_list_stream`. The streams that
+    /// have been dropped are forgotten.
+    pub fn handle_available_themes(&mut self, themes: &AvailableThemes) {
+        self.themes = Some(themes.themes.clone());
+        self.themes_subscribers
+            .retain(|subscriber| subscriber.unbounded_send(themes.themes.clone()).is_ok());
+    }
+
+    /// Forward a notification to the subscribers interested in the plugin
+    /// it comes from, if any. The plugin is identified by the `"plugin"` or
+    /// `"source"` field of the parameters, as in `"plugin_started"` or in
+    /// the status item notifications.
+    pub fn handle_plugin_notification(&mut self, method: &str, params: &Value) {
+        if self.plugin_subscribers.is_empty() {
+            return;
+        }
+        let plugin = match params
+            .get("plugin")
+            .or_else(|| params.get("source"))
+            .and_then(Value::as_str)
+        {
+            Some(plugin) => plugin.to_string(),
+            None => return,
+        };
+        self.plugin_subscribers.retain(|(name, subscriber)| {
+            *name != plugin || subscriber
+                .unbounded_send((method.to_string(), params.clone()))
+                .is_ok()
+        });
+    }
+
+    /// Record the language of a view, and wake up the futures waiting for
+    /// this language to be applied.
+    pub fn handle_language_changed(&mut self, language: &LanguageChanged) {
+        let view = self.view_mut(language.view_id);
+        view.language = Some(language.language_id.clone());
+        let (ready, waiting) = view
+            .language_waiters
+            .drain(..)
+            .partition(|(name, _)| *name == language.language_id);
+        view.language_waiters = waiting;
+        for (_, waiter) in ready {
+            let _ = waiter.send(());
+        }
+    }
+
+    /// Send the status of the searches of a view to the streams returned by
+    /// `Client::find_all_paged`. The streams that have been dropped are
+    /// forgotten.
+    pub fn handle_find_status(&mut self, status: &FindStatusChanged) {
+        self.view_mut(status.view_id)
+            .find_status_subscribers
+            .retain(|subscriber| subscriber.unbounded_send(status.clone()).is_ok());
+    }
+
+    /// Record the theme and its colors, and wake up the futures waiting for
+    /// it to be applied.
+    pub fn handle_theme_changed(&mut self, theme: &ThemeChanged) {
+        self.theme = Some(theme.name.clone());
+        self.theme_settings.insert(theme.name.clone(), theme.theme.clone());
+        let (ready, waiting) = self
+            .theme_waiters
+            .drain(..)
+            .partition(|(name, _)| *name == theme.name);
+        self.theme_waiters = waiting;
+        for (_, waiter) in ready {
+            let _ = waiter.send(theme.clone());
+        }
+    }
+
+    /// Record that a plugin has been started or stopped for a view. The
+    /// core also sends these notifications when a plugin exits by itself,
+    /// for instance when it crashes.
+    pub fn handle_plugin_status(&mut self, status: &PluginStatus) {
+        let plugins = &mut self.view_mut(status.view_id).plugins;
+        if status.running {
+            let _ = plugins.insert(status.plugin.clone());
+        } else {
+            let _ = plugins.remove(&status.plugin);
+        }
+    }
+
+    /// Record the changes of a view's configuration.
+    pub fn handle_config_changed(&mut self, config: &ConfigChanged) {
+        if let Some(ref line_ending) = config.changes.line_ending {
+            self.view_mut(config.view_id).line_ending = LineEnding::from_setting(line_ending);
+        }
+    }
+
+    /// Record a style defined by a `"def_style"` notification.
+    pub fn handle_def_style(&mut self, style: &Style) {
+        let _ = self.styles.insert(style.id, style.clone());
+    }
+}
+
+/// Answer a request with the handler registered for its method, if any.
+/// The state is not locked while the handler runs, so that the handler can
+/// use the `Client`.
+pub fn handle_request(state: &SharedState, method: &str, params: Value) -> Option<Result<Value, Value>> {
+    let mut handler = state.lock().unwrap().request_handlers.remove(method)?;
+    let result = (handler.0)(params);
+    let _ = state
+        .lock()
+        .unwrap()
+        .request_handlers
+        .entry(method.to_string())
+        .or_insert(handler);
+    Some(result)
+}
+
+#[test]
+fn plugin_notifications_are_filtered_by_plugin() {
+    use futures::{Future, Stream};
+
+    let mut state = ClientState::default();
+    let (tx, rx) = mpsc::unbounded();
+    state.plugin_subscribers.push(("blame".to_string(), tx));
+    state.handle_plugin_notification("add_status_item", &json!({"source": "syntect"}));
+    state.handle_plugin_notification("add_status_item", &json!({"source": "blame"}));
+    state.plugin_subscribers.clear();
+    let received = rx.collect().wait().unwrap();
+    assert_eq!(
+        received,
+        vec![("add_status_item".to_string(), json!({"source": "blame"}))]
+    );
+}
+
+#[test]
+fn pristine_transitions_are_streamed() {
+    use futures::{Future, Stream};
+
+    let update = |pristine| Update {
+        rev: None,
+        operations: vec![],
+        annotations: vec![],
+        pristine,
+        view_id: ViewId(1),
+    };
+    let mut state = ClientState::default();
+    let (tx, rx) = mpsc::unbounded();
+    state.view_mut(ViewId(1)).pristine_subscribers.push(tx);
+    state.handle_update(&update(true));
+    state.handle_update(&update(false));
+    state.handle_update(&update(false));
+    state.handle_update(&update(true));
+    state.view_mut(ViewId(1)).pristine_subscribers.clear();
+    let received = rx.collect().wait().unwrap();
+    assert_eq!(received, vec![true, false, true]);
+}
+
+#[test]
+fn requests_are_answered_by_their_handler() {
+    let state: SharedState = Default::default();
+    let handler = RequestHandler(Box::new(|params: Value| Ok(json!({"echo": params}))));
+    let _ = state
+        .lock()
+        .unwrap()
+        .request_handlers
+        .insert("get_data".to_string(), handler);
+    assert_eq!(
+        handle_request(&state, "get_data", json!(1)),
+        Some(Ok(json!({"echo": 1})))
+    );
+    assert_eq!(
+        handle_request(&state, "get_data", json!(2)),
+        Some(Ok(json!({"echo": 2})))
+    );
+    assert_eq!(handle_request(&state, "other", json!(1)), None);
+}
+
+#[test]
+fn language_waiters_are_woken_by_their_language() {
+    let mut state = ClientState::default();
+    let (tx, mut rx) = oneshot::channel();
+    state.view_mut(ViewId(1)).language_waiters.push(("Rust".to_string(), tx));
+    let changed = |language: &str| LanguageChanged {
+        view_id: ViewId(1),
+        language_id: language.to_string(),
+    };
+    state.handle_language_changed(&changed("Markdown"));
+    assert_eq!(rx.try_recv(), Ok(None));
+    state.handle_language_changed(&changed("Rust"));
+    assert_eq!(rx.try_recv(), Ok(Some(())));
+    assert_eq!(state.view_mut(ViewId(1)).language, Some("Rust".to_string()));
+}
+
+#[test]
+fn connection_events_are_streamed() {
+    use futures::{Future, Stream};
+
+    let mut state = ClientState::default();
+    let (tx, rx) = mpsc::unbounded();
+    state.connection_subscribers.push(tx);
+    state.handle_connection_event(ConnectionEvent::Connected);
+    let disconnected = ConnectionEvent::Disconnected { reason: "connection closed".into() };
+    state.handle_connection_event(disconnected.clone());
+    state.connection_subscribers.clear();
+    assert_eq!(rx.collect().wait().unwrap(), vec![ConnectionEvent::Connected, disconnected.clone()]);
+    assert_eq!(state.connection, Some(disconnected));
+}
+
+#[test]
+fn theme_settings_are_cached() {
+    let mut state = ClientState::default();
+    let theme = ThemeChanged { name: "Solarized (dark)".into(), theme: ThemeSettings::default() };
+    state.handle_theme_changed(&theme);
+    assert_eq!(state.theme, Some("Solarized (dark)".to_string()));
+    assert!(state.theme_settings.contains_key("Solarized (dark)"));
+}
@@ -0,0 +1,16 @@
+/// Parameters of the `"alert"` notification, sent by the core to ask the
+/// frontend to show a message to the user, for instance when a file
+/// changed on disk.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub msg: String,
+}
+
+#[test]
+fn deserialize_alert() {
+    use serde_json;
+
+    let s = r#"{"msg":"file changed on disk"}"#;
+    let deserialized: Alert = serde_json::from_str(s).unwrap();
+    assert_eq!(deserialized.msg, "file changed on disk");
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+use Position;
+
+/// A set of ranges of a view that the core attaches a meaning to, sent
+/// with the `"update"` notifications. The core annotates the selections
+/// (`"selection"`) and the matches of the current search (`"find"`), and
+/// plugins may add their own types, for instance for diagnostics.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct Annotation {
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// The annotated ranges, as `(start, end)` positions.
+    #[serde(deserialize_with = "deserialize_ranges")]
+    pub ranges: Vec<(Position, Position)>,
+    /// Data attached to each range, such as diagnostic messages, if any.
+    #[serde(default)]
+    pub payloads: Option<Value>,
+}
+
+/// The core sends each range as a `[start_line, start_col, end_line,
+/// end_col]` array.
+fn deserialize_ranges<'de, D>(de: D) -> Result<Vec<(Position, Position)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ranges: Vec<[u64; 4]> = Deserialize::deserialize(de)?;
+    Ok(ranges
+        .into_iter()
+        .map(|r| (Position::new(r[0], r[1]), Position::new(r[2], r[3])))
+        .collect())
+}
+
+#[test]
+fn deserialize_annotation() {
+    use serde_json;
+
+    let s = r#"{"type":"find","n":2,"ranges":[[0,1,0,4],[2,0,3,2]],"payloads":null}"#;
+    let deserialized: Annotation = serde_json::from_str(s).unwrap();
+    assert_eq!(deserialized.ty, "find");
+    assert_eq!(
+        deserialized.ranges,
+        vec![
+            (Position::new(0, 1), Position::new(0, 4)),
+            (Position::new(2, 0), Position::new(3, 2)),
+        ]
+    );
+    assert_eq!(deserialized.payloads, None);
+}
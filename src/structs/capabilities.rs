@@ -0,0 +1,23 @@
+/// Capabilities of the frontend, sent to the core with the
+/// `"client_started"` notification so that it can tailor what it sends.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct ClientCapabilities {
+    /// Whether the frontend wraps long lines itself.
+    pub line_wrap: bool,
+    /// Whether the frontend renders the style spans sent with the lines.
+    pub spans: bool,
+}
+
+#[test]
+fn serialize_client_capabilities() {
+    use serde_json;
+
+    let capabilities = ClientCapabilities {
+        line_wrap: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        json!({"line_wrap": true, "spans": false}),
+        serde_json::to_value(&capabilities).unwrap()
+    );
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use ViewId;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -15,3 +17,126 @@ pub struct ConfigChanges {
     pub tab_size: Option<u64>,
     pub translate_tabs_to_spaces: Option<bool>,
 }
+
+fn default_font_face() -> String {
+    "InconsolataGo".to_string()
+}
+fn default_font_size() -> u64 {
+    14
+}
+fn default_line_ending() -> String {
+    "\n".to_string()
+}
+fn default_tab_size() -> u64 {
+    4
+}
+fn default_translate_tabs_to_spaces() -> bool {
+    true
+}
+
+/// The effective configuration of a view, as returned by the `"get_config"`
+/// request. The settings the core does not return get the core's default
+/// value.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default = "default_font_face")]
+    pub font_face: String,
+    #[serde(default = "default_font_size")]
+    pub font_size: u64,
+    #[serde(default = "default_line_ending")]
+    pub line_ending: String,
+    #[serde(default)]
+    pub plugin_search_path: Vec<String>,
+    #[serde(default = "default_tab_size")]
+    pub tab_size: u64,
+    #[serde(default = "default_translate_tabs_to_spaces")]
+    pub translate_tabs_to_spaces: bool,
+}
+
+/// The domain a configuration change applies to, used by
+/// `"modify_user_config"`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigDomain {
+    /// The general settings, shared by all the views.
+    General,
+    /// The settings of a given syntax, for instance `"rust"`.
+    Syntax(String),
+    /// The settings of a single view, overriding all the others.
+    UserOverride(ViewId),
+}
+
+/// The line ending of a document, as used by the `"line_ending"` setting.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum LineEnding {
+    #[serde(rename = "\n")]
+    Lf,
+    #[serde(rename = "\r\n")]
+    CrLf,
+}
+
+impl LineEnding {
+    /// Parse the value of the `"line_ending"` setting.
+    pub fn from_setting(setting: &str) -> Option<LineEnding> {
+        match setting {
+            "\n" => Some(LineEnding::Lf),
+            "\r\n" => Some(LineEnding::CrLf),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::CrLf => write!(f, "CRLF"),
+        }
+    }
+}
+
+#[test]
+fn line_ending_setting() {
+    use serde_json;
+
+    assert_eq!(json!("\r\n"), serde_json::to_value(&LineEnding::CrLf).unwrap());
+    assert_eq!(LineEnding::from_setting("\n"), Some(LineEnding::Lf));
+    assert_eq!(LineEnding::from_setting("\r"), None);
+    assert_eq!(LineEnding::CrLf.to_string(), "CRLF");
+}
+
+#[test]
+fn deserialize_config_with_defaults() {
+    use serde_json;
+
+    let config: Config = serde_json::from_value(json!({"tab_size": 2})).unwrap();
+    assert_eq!(config.tab_size, 2);
+    assert_eq!(config.font_size, 14);
+    assert_eq!(config.line_ending, "\n");
+    assert!(config.translate_tabs_to_spaces);
+    assert!(config.plugin_search_path.is_empty());
+}
+
+#[test]
+fn serialize_config_domain() {
+    use serde_json;
+
+    assert_eq!(json!("general"), serde_json::to_value(&ConfigDomain::General).unwrap());
+    assert_eq!(
+        json!({"syntax": "rust"}),
+        serde_json::to_value(&ConfigDomain::Syntax("rust".into())).unwrap()
+    );
+    assert_eq!(
+        json!({"user_override": "view-id-1"}),
+        serde_json::to_value(&ConfigDomain::UserOverride(ViewId(1))).unwrap()
+    );
+}
+
+#[test]
+fn deserialize_config_changed_with_unknown_fields() {
+    use serde_json;
+
+    let s = r#"{"view_id":"view-id-1","changes":{"tab_size":2,"word_wrap":true,"autodetect_whitespace":true}}"#;
+    let config: ConfigChanged = serde_json::from_str(s).unwrap();
+    assert_eq!(config.changes.tab_size, Some(2));
+}
@@ -0,0 +1,10 @@
+/// A change of the state of the connection with the core. See
+/// `Client::connection_events`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConnectionEvent {
+    /// The core has been started, and messages can be exchanged with it.
+    Connected,
+    /// The connection with the core has been closed, because the core
+    /// exited, the client shut it down, or an I/O error occurred.
+    Disconnected { reason: String },
+}
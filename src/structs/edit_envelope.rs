@@ -0,0 +1,101 @@
+use serde_json::Value;
+
+use ViewId;
+
+/// What is sent as the parameters of an edit command that is given none.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EmptyParams {
+    /// An empty object or array, depending on what xi-core expects for the
+    /// command, since it ignores a command whose parameters have the wrong
+    /// shape. See `EditMethod::takes_object_params`.
+    PerMethod,
+    /// Always an empty array.
+    Array,
+    /// Always an empty object.
+    Object,
+    /// No parameters at all: the `"params"` field is left out.
+    Omit,
+}
+
+impl Default for EmptyParams {
+    fn default() -> Self {
+        EmptyParams::PerMethod
+    }
+}
+
+/// How the `"edit"` commands are wrapped in the messages sent to the core.
+/// xi-core expects the `Nested` shape, but forks of the core may expect
+/// another one, which can be provided with `Custom`.
+#[derive(Debug, Clone, Copy)]
+pub enum EditEnvelope {
+    /// An `"edit"` message, whose parameters hold the command, the view and
+    /// the parameters of the command:
+    ///
+    /// ```ignore
+    /// {"method":"edit","params":{"method":"insert","view_id":"view-id-1",
+    /// "params":{"chars":"a"}}}
+    /// ```
+    Nested,
+    /// A function returning the method and the parameters of the message,
+    /// given the view, the command, and the parameters of the command, if
+    /// any (see `EmptyParams::Omit`).
+    Custom(fn(ViewId, &str, Option<Value>) -> (String, Value)),
+}
+
+impl Default for EditEnvelope {
+    fn default() -> Self {
+        EditEnvelope::Nested
+    }
+}
+
+impl EditEnvelope {
+    /// Return the method and the parameters of the message carrying a
+    /// command. With the `Nested` envelope, the `"params"` field is left
+    /// out if `params` is `None`.
+    pub fn wrap(&self, view_id: ViewId, method: &str, params: Option<Value>) -> (String, Value) {
+        match *self {
+            EditEnvelope::Nested => {
+                let mut edit = json!({"method": method, "view_id": view_id});
+                if let Some(params) = params {
+                    edit["params"] = params;
+                }
+                ("edit".to_string(), edit)
+            }
+            EditEnvelope::Custom(wrap) => wrap(view_id, method, params),
+        }
+    }
+
+    pub fn is_nested(&self) -> bool {
+        match *self {
+            EditEnvelope::Nested => true,
+            EditEnvelope::Custom(_) => false,
+        }
+    }
+}
+
+#[test]
+fn wrap_edit_commands() {
+    let params = json!({"chars": "a"});
+    assert_eq!(
+        EditEnvelope::default().wrap(ViewId(1), "insert", Some(params.clone())),
+        (
+            "edit".to_string(),
+            json!({"method": "insert", "view_id": "view-id-1", "params": {"chars": "a"}})
+        )
+    );
+
+    fn flat(view_id: ViewId, method: &str, params: Option<Value>) -> (String, Value) {
+        (method.to_string(), json!({"view_id": view_id, "args": params}))
+    }
+    assert_eq!(
+        EditEnvelope::Custom(flat).wrap(ViewId(1), "insert", Some(params)),
+        (
+            "insert".to_string(),
+            json!({"view_id": "view-id-1", "args": {"chars": "a"}})
+        )
+    );
+    assert_eq!(
+        EditEnvelope::default().wrap(ViewId(1), "move_up", None).1,
+        json!({"method": "move_up", "view_id": "view-id-1"})
+    );
+}
@@ -0,0 +1,226 @@
+/// The methods of the `"edit"` commands sent to the core.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum EditMethod {
+    AddSelectionAbove,
+    AddSelectionBelow,
+    Click,
+    CollapseSelections,
+    Copy,
+    Cut,
+    DebugRewrap,
+    DebugTestFgSpans,
+    DeleteBackward,
+    DeleteForward,
+    Drag,
+    Find,
+    FindAll,
+    FindNext,
+    FindPrevious,
+    Gesture,
+    GotoLine,
+    HighlightFind,
+    Indent,
+    Insert,
+    InsertNewline,
+    InsertTab,
+    MoveDown,
+    MoveDownAndModifySelection,
+    MoveLeft,
+    MoveLeftAndModifySelection,
+    MoveRight,
+    MoveRightAndModifySelection,
+    MoveToBeginningOfDocument,
+    MoveToLeftEndOfLine,
+    MoveToLeftEndOfLineAndModifySelection,
+    MoveToRightEndOfLine,
+    MoveToRightEndOfLineAndModifySelection,
+    MoveUp,
+    MoveUpAndModifySelection,
+    Outdent,
+    PageDownAndModifySelection,
+    PageUpAndModifySelection,
+    Paste,
+    Redo,
+    Replace,
+    ReplaceAll,
+    ReplaceNext,
+    RequestLines,
+    Scroll,
+    ScrollPageDown,
+    ScrollPageUp,
+    SelectAll,
+    SelectionForFind,
+    SelectionForReplace,
+    ToggleComment,
+    Undo,
+}
+
+impl EditMethod {
+    /// All the edit methods, for instance to list them in a command
+    /// palette.
+    pub const ALL: &'static [EditMethod] = &[
+        EditMethod::AddSelectionAbove,
+        EditMethod::AddSelectionBelow,
+        EditMethod::Click,
+        EditMethod::CollapseSelections,
+        EditMethod::Copy,
+        EditMethod::Cut,
+        EditMethod::DebugRewrap,
+        EditMethod::DebugTestFgSpans,
+        EditMethod::DeleteBackward,
+        EditMethod::DeleteForward,
+        EditMethod::Drag,
+        EditMethod::Find,
+        EditMethod::FindAll,
+        EditMethod::FindNext,
+        EditMethod::FindPrevious,
+        EditMethod::Gesture,
+        EditMethod::GotoLine,
+        EditMethod::HighlightFind,
+        EditMethod::Indent,
+        EditMethod::Insert,
+        EditMethod::InsertNewline,
+        EditMethod::InsertTab,
+        EditMethod::MoveDown,
+        EditMethod::MoveDownAndModifySelection,
+        EditMethod::MoveLeft,
+        EditMethod::MoveLeftAndModifySelection,
+        EditMethod::MoveRight,
+        EditMethod::MoveRightAndModifySelection,
+        EditMethod::MoveToBeginningOfDocument,
+        EditMethod::MoveToLeftEndOfLine,
+        EditMethod::MoveToLeftEndOfLineAndModifySelection,
+        EditMethod::MoveToRightEndOfLine,
+        EditMethod::MoveToRightEndOfLineAndModifySelection,
+        EditMethod::MoveUp,
+        EditMethod::MoveUpAndModifySelection,
+        EditMethod::Outdent,
+        EditMethod::PageDownAndModifySelection,
+        EditMethod::PageUpAndModifySelection,
+        EditMethod::Paste,
+        EditMethod::Redo,
+        EditMethod::Replace,
+        EditMethod::ReplaceAll,
+        EditMethod::ReplaceNext,
+        EditMethod::RequestLines,
+        EditMethod::Scroll,
+        EditMethod::ScrollPageDown,
+        EditMethod::ScrollPageUp,
+        EditMethod::SelectAll,
+        EditMethod::SelectionForFind,
+        EditMethod::SelectionForReplace,
+        EditMethod::ToggleComment,
+        EditMethod::Undo,
+    ];
+
+    /// The name of the method, as sent to the core.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EditMethod::AddSelectionAbove => "add_selection_above",
+            EditMethod::AddSelectionBelow => "add_selection_below",
+            EditMethod::Click => "click",
+            EditMethod::CollapseSelections => "collapse_selections",
+            EditMethod::Copy => "copy",
+            EditMethod::Cut => "cut",
+            EditMethod::DebugRewrap => "debug_rewrap",
+            EditMethod::DebugTestFgSpans => "debug_test_fg_spans",
+            EditMethod::DeleteBackward => "delete_backward",
+            EditMethod::DeleteForward => "delete_forward",
+            EditMethod::Drag => "drag",
+            EditMethod::Find => "find",
+            EditMethod::FindAll => "find_all",
+            EditMethod::FindNext => "find_next",
+            EditMethod::FindPrevious => "find_previous",
+            EditMethod::Gesture => "gesture",
+            EditMethod::GotoLine => "goto_line",
+            EditMethod::HighlightFind => "highlight_find",
+            EditMethod::Indent => "indent",
+            EditMethod::Insert => "insert",
+            EditMethod::InsertNewline => "insert_newline",
+            EditMethod::InsertTab => "insert_tab",
+            EditMethod::MoveDown => "move_down",
+            EditMethod::MoveDownAndModifySelection => "move_down_and_modify_selection",
+            EditMethod::MoveLeft => "move_left",
+            EditMethod::MoveLeftAndModifySelection => "move_left_and_modify_selection",
+            EditMethod::MoveRight => "move_right",
+            EditMethod::MoveRightAndModifySelection => "move_right_and_modify_selection",
+            EditMethod::MoveToBeginningOfDocument => "move_to_beginning_of_document",
+            EditMethod::MoveToLeftEndOfLine => "move_to_left_end_of_line",
+            EditMethod::MoveToLeftEndOfLineAndModifySelection => "move_to_left_end_of_line_and_modify_selection",
+            EditMethod::MoveToRightEndOfLine => "move_to_right_end_of_line",
+            EditMethod::MoveToRightEndOfLineAndModifySelection => "move_to_right_end_of_line_and_modify_selection",
+            EditMethod::MoveUp => "move_up",
+            EditMethod::MoveUpAndModifySelection => "move_up_and_modify_selection",
+            EditMethod::Outdent => "outdent",
+            EditMethod::PageDownAndModifySelection => "page_down_and_modify_selection",
+            EditMethod::PageUpAndModifySelection => "page_up_and_modify_selection",
+            EditMethod::Paste => "paste",
+            EditMethod::Redo => "redo",
+            EditMethod::Replace => "replace",
+            EditMethod::ReplaceAll => "replace_all",
+            EditMethod::ReplaceNext => "replace_next",
+            EditMethod::RequestLines => "request_lines",
+            EditMethod::Scroll => "scroll",
+            EditMethod::ScrollPageDown => "scroll_page_down",
+            EditMethod::ScrollPageUp => "scroll_page_up",
+            EditMethod::SelectAll => "select_all",
+            EditMethod::SelectionForFind => "selection_for_find",
+            EditMethod::SelectionForReplace => "selection_for_replace",
+            EditMethod::ToggleComment => "toggle_comment",
+            EditMethod::Undo => "undo",
+        }
+    }
+
+    /// Return the method with the given name, if it is known.
+    pub fn from_name(name: &str) -> Option<EditMethod> {
+        EditMethod::ALL.iter().find(|method| method.as_str() == name).cloned()
+    }
+
+    /// Whether the parameters of the method are an object rather than an
+    /// array. The core silently ignores a command whose parameters have
+    /// the wrong shape, so this determines what is sent when a command is
+    /// given no parameters.
+    #[allow(clippy::match_like_matches_macro)]
+    pub fn takes_object_params(&self) -> bool {
+        match *self {
+            EditMethod::Find
+            | EditMethod::FindNext
+            | EditMethod::FindPrevious
+            | EditMethod::Gesture
+            | EditMethod::GotoLine
+            | EditMethod::HighlightFind
+            | EditMethod::Insert
+            | EditMethod::Paste
+            | EditMethod::Replace
+            | EditMethod::SelectionForFind => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the method is a request, that the core answers, rather
+    /// than a notification.
+    pub fn is_request(&self) -> bool {
+        *self == EditMethod::Copy || *self == EditMethod::Cut
+    }
+}
+
+impl AsRef<str> for EditMethod {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+#[test]
+fn edit_method_names() {
+    assert_eq!(EditMethod::MoveToLeftEndOfLine.as_str(), "move_to_left_end_of_line");
+    assert_eq!(
+        EditMethod::from_name("move_to_beginning_of_document"),
+        Some(EditMethod::MoveToBeginningOfDocument)
+    );
+    assert!(EditMethod::Copy.is_request());
+    assert!(!EditMethod::Insert.is_request());
+    let requests: Vec<_> = EditMethod::ALL.iter().filter(|m| m.is_request()).collect();
+    assert_eq!(requests, vec![&EditMethod::Copy, &EditMethod::Cut]);
+    assert_eq!(EditMethod::from_name("find_all"), Some(EditMethod::FindAll));
+    assert_eq!(EditMethod::from_name("no_such_method"), None);
+}
@@ -0,0 +1,88 @@
+use ViewId;
+
+/// The status of a search query, as reported by the `"find_status"`
+/// notification.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FindStatus {
+    /// Id of the query.
+    pub id: u64,
+    /// The searched text, or `None` if there is no query.
+    pub chars: Option<String>,
+    pub case_sensitive: Option<bool>,
+    pub is_regex: Option<bool>,
+    pub whole_words: Option<bool>,
+    /// Number of matches in the document.
+    pub matches: u64,
+    /// Lines containing a match.
+    #[serde(default)]
+    pub lines: Vec<u64>,
+}
+
+/// Parameters of the `"find_status"` notification, sent when the search
+/// queries of a view or their matches change.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FindStatusChanged {
+    pub view_id: ViewId,
+    pub queries: Vec<FindStatus>,
+}
+
+/// A match of a search query, as reported by the `"find_status"`
+/// notification. The core only reports the line of each match.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FindMatch {
+    /// Id of the query.
+    pub query_id: u64,
+    pub line: u64,
+}
+
+/// The status of the replacement, as reported by the `"replace_status"`
+/// notification.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ReplaceStatus {
+    /// The replacement text.
+    pub chars: String,
+    pub preserve_case: Option<bool>,
+}
+
+/// Parameters of the `"replace_status"` notification, sent when the
+/// replacement text of a view changes.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ReplaceStatusChanged {
+    pub view_id: ViewId,
+    pub status: ReplaceStatus,
+}
+
+#[test]
+fn deserialize_find_status() {
+    use serde_json;
+
+    let s = r#"{"view_id":"view-id-1","queries":[{"id":1,"chars":"a",
+        "case_sensitive":false,"is_regex":false,"whole_words":true,
+        "matches":6,"lines":[1,3,3,6]}]}"#;
+    let deserialized: FindStatusChanged = serde_json::from_str(s).unwrap();
+    assert_eq!(
+        deserialized,
+        FindStatusChanged {
+            view_id: ViewId(1),
+            queries: vec![FindStatus {
+                id: 1,
+                chars: Some("a".into()),
+                case_sensitive: Some(false),
+                is_regex: Some(false),
+                whole_words: Some(true),
+                matches: 6,
+                lines: vec![1, 3, 3, 6],
+            }],
+        }
+    );
+}
+
+#[test]
+fn deserialize_replace_status() {
+    use serde_json;
+
+    let s = r#"{"view_id":"view-id-1","status":{"chars":"b","preserve_case":null}}"#;
+    let deserialized: ReplaceStatusChanged = serde_json::from_str(s).unwrap();
+    assert_eq!(deserialized.status.chars, "b");
+    assert_eq!(deserialized.status.preserve_case, None);
+}
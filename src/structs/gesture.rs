@@ -0,0 +1,88 @@
+use serde::ser::{Serialize, Serializer};
+
+/// The type of a `"gesture"` command.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureType {
+    PointSelect,
+    ToggleSel,
+    RangeSelect,
+    LineSelect,
+    WordSelect,
+    MultiLineSelect,
+    MultiWordSelect,
+}
+
+/// The unit by which a selection is extended when dragging.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionGranularity {
+    Char,
+    Word,
+    Line,
+}
+
+impl Default for SelectionGranularity {
+    fn default() -> Self {
+        SelectionGranularity::Char
+    }
+}
+
+/// The modifier keys held during a gesture. They are serialized as the
+/// bit flags used by the `"click"` command.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ClickModifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl ClickModifiers {
+    pub fn flags(&self) -> u64 {
+        let mut flags = 0;
+        if self.shift {
+            flags |= 1 << 1;
+        }
+        if self.control {
+            flags |= 1 << 2;
+        }
+        if self.alt {
+            flags |= 1 << 3;
+        }
+        if self.meta {
+            flags |= 1 << 4;
+        }
+        flags
+    }
+}
+
+impl Serialize for ClickModifiers {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.flags())
+    }
+}
+
+#[test]
+fn serialize_gesture() {
+    use serde_json;
+
+    assert_eq!(
+        json!("multi_word_select"),
+        serde_json::to_value(&GestureType::MultiWordSelect).unwrap()
+    );
+    let modifiers = ClickModifiers {
+        shift: true,
+        meta: true,
+        ..Default::default()
+    };
+    assert_eq!(json!(18), serde_json::to_value(&modifiers).unwrap());
+    assert_eq!(json!(0), serde_json::to_value(&ClickModifiers::default()).unwrap());
+    assert_eq!(
+        json!("word"),
+        serde_json::to_value(&SelectionGranularity::Word).unwrap()
+    );
+}
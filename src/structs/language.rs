@@ -0,0 +1,39 @@
+use ViewId;
+
+/// Parameters of the `"available_languages"` notification, that lists the
+/// languages supported by the core.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AvailableLanguages {
+    pub languages: Vec<String>,
+}
+
+/// Parameters of the `"language_changed"` notification, sent when the
+/// language of a view changes, either after a `"set_language"` or because
+/// the core detected it when the file was opened.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LanguageChanged {
+    pub view_id: ViewId,
+    pub language_id: String,
+}
+
+#[test]
+fn deserialize_available_languages() {
+    use serde_json;
+
+    let s = r#"{"languages":["Plain Text","Rust","Markdown"]}"#;
+    let deserialized: Result<AvailableLanguages, _> = serde_json::from_str(s);
+    let languages = AvailableLanguages {
+        languages: vec!["Plain Text".into(), "Rust".into(), "Markdown".into()],
+    };
+    assert_eq!(deserialized.unwrap(), languages);
+}
+
+#[test]
+fn deserialize_language_changed() {
+    use serde_json;
+
+    let s = r#"{"view_id":"view-id-1","language_id":"Rust"}"#;
+    let deserialized: LanguageChanged = serde_json::from_str(s).unwrap();
+    assert_eq!(deserialized.view_id, ViewId(1));
+    assert_eq!(deserialized.language_id, "Rust");
+}
@@ -1,5 +1,7 @@
 use serde::{self, Deserialize, Deserializer};
 
+use super::{Style, StyleMap};
+
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct StyleDef {
     pub offset: i64,
@@ -13,10 +15,58 @@ pub struct Line {
     pub text: String,
     #[serde(default)]
     pub cursor: Vec<u64>,
-    #[serde(deserialize_with = "deserialize_styles")]
+    #[serde(default, deserialize_with = "deserialize_styles")]
     pub styles: Vec<StyleDef>,
 }
 
+/// A span of a `StyledLine`, with its style resolved.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyledSpan {
+    /// Byte offset of the start of the span in the line.
+    pub start: u64,
+    /// Byte offset of the end of the span in the line (exclusive).
+    pub end: u64,
+    pub style_id: u64,
+    /// The style defined by the core for `style_id`, or `None` if the core
+    /// did not define it, like the reserved selection and find styles.
+    pub style: Option<Style>,
+}
+
+/// A line with the styles of its spans resolved, ready to be rendered.
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct StyledLine {
+    pub text: String,
+    pub cursor: Vec<u64>,
+    pub spans: Vec<StyledSpan>,
+}
+
+impl Line {
+    /// Resolve the styles of the line with the given style map. The offset
+    /// of each `StyleDef` is relative to the end of the previous one.
+    pub fn styled(&self, styles: &StyleMap) -> StyledLine {
+        let mut end: i64 = 0;
+        let spans = self
+            .styles
+            .iter()
+            .map(|def| {
+                let start = (end + def.offset).max(0) as u64;
+                end = (start + def.length) as i64;
+                StyledSpan {
+                    start,
+                    end: end as u64,
+                    style_id: def.style_id,
+                    style: styles.get(&def.style_id).cloned(),
+                }
+            })
+            .collect();
+        StyledLine {
+            text: self.text.clone(),
+            cursor: self.cursor.clone(),
+            spans,
+        }
+    }
+}
+
 // FIXME: it's not super efficient to create an intermediate vector, this might
 // become a problem when we have big updates with a lot of styles.
 pub fn deserialize_styles<'de, D>(deserializer: D) -> Result<Vec<StyleDef>, D::Error>
@@ -89,3 +139,28 @@ fn deserialize_line_with_no_style() {
     let deserialized: Result<Line, _> = serde_json::from_str(s);
     assert_eq!(deserialized.unwrap(), line);
 }
+
+#[test]
+fn styled_line_resolves_relative_offsets() {
+    let line = Line {
+        text: "foo bar baz".to_string(),
+        cursor: vec![],
+        styles: vec![
+            StyleDef { offset: 0, length: 3, style_id: 2 },
+            StyleDef { offset: 5, length: 3, style_id: 0 },
+        ],
+    };
+    let style = Style { id: 2, fg_color: Some(0xff00_00ff), ..Style::default() };
+    let mut styles = StyleMap::new();
+    styles.insert(2, style.clone());
+
+    let styled = line.styled(&styles);
+    assert_eq!(styled.text, line.text);
+    assert_eq!(
+        styled.spans,
+        vec![
+            StyledSpan { start: 0, end: 3, style_id: 2, style: Some(style) },
+            StyledSpan { start: 8, end: 11, style_id: 0, style: None },
+        ]
+    );
+}
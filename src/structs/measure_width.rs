@@ -0,0 +1,31 @@
+/// A batch of strings that should be measured with the style `id`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MeasureWidthItem {
+    pub id: u64,
+    pub strings: Vec<String>,
+}
+
+/// Parameters of the `"measure_width"` request sent by `xi-core`. The
+/// frontend must answer with one vector of widths per item, in the same
+/// order.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MeasureWidth(pub Vec<MeasureWidthItem>);
+
+#[test]
+fn deserialize_ok() {
+    use serde_json;
+
+    let s = r#"[{"id":0,"strings":["hello","world"]},{"id":2,"strings":[]}]"#;
+    let deserialized: Result<MeasureWidth, _> = serde_json::from_str(s);
+    let measure_width = MeasureWidth(vec![
+        MeasureWidthItem {
+            id: 0,
+            strings: vec!["hello".to_string(), "world".to_string()],
+        },
+        MeasureWidthItem {
+            id: 2,
+            strings: vec![],
+        },
+    ]);
+    assert_eq!(deserialized.unwrap(), measure_width);
+}
@@ -8,27 +8,49 @@ mod plugins;
 mod config;
 mod view;
 mod modifyselection;
+mod measure_width;
+mod theme;
+mod selection;
+mod language;
+mod symbol;
+mod gesture;
+mod find;
+mod capabilities;
+mod alert;
+mod edit_method;
+mod annotation;
+mod edit_envelope;
+mod connection;
 
-pub use self::line::{Line, StyleDef};
+pub use self::line::{Line, StyleDef, StyledLine, StyledSpan};
 pub use self::operation::{Operation, OperationType};
-pub use self::style::Style;
+pub use self::style::{Style, StyleMap};
 pub use self::update::Update;
-pub use self::position::Position;
+pub use self::position::{byte_to_utf16_col, utf16_to_byte_col, Position};
 pub use self::scroll_to::ScrollTo;
 pub use self::plugins::AvailablePlugins;
 pub use self::plugins::Plugin;
 pub use self::plugins::PluginStarted;
 pub use self::plugins::PluginStoped;
+pub use self::plugins::PluginStatus;
 pub use self::plugins::UpdateCmds;
+pub use self::config::Config;
 pub use self::config::ConfigChanged;
 pub use self::config::ConfigChanges;
+pub use self::config::ConfigDomain;
+pub use self::config::LineEnding;
 pub use self::view::ViewId;
-pub use self::modifyselection::ModifySelection;
-
-pub type ThemeSettings = ::syntect::highlighting::ThemeSettings;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ThemeChanged {
-    pub name: String,
-    pub theme: ThemeSettings
-}
+pub use self::modifyselection::{ModifySelection, SelectionMode};
+pub use self::measure_width::{MeasureWidth, MeasureWidthItem};
+pub use self::theme::{AvailableThemes, ThemeChanged, ThemeSettings};
+pub use self::selection::SelRegion;
+pub use self::language::{AvailableLanguages, LanguageChanged};
+pub use self::symbol::Symbol;
+pub use self::gesture::{ClickModifiers, GestureType, SelectionGranularity};
+pub use self::capabilities::ClientCapabilities;
+pub use self::alert::Alert;
+pub use self::edit_method::EditMethod;
+pub use self::annotation::Annotation;
+pub use self::edit_envelope::{EditEnvelope, EmptyParams};
+pub use self::connection::ConnectionEvent;
+pub use self::find::{FindMatch, FindStatus, FindStatusChanged, ReplaceStatus, ReplaceStatusChanged};
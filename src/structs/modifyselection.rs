@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ModifySelection {
     None,
@@ -7,6 +7,23 @@ pub enum ModifySelection {
     AddRemoveCurrent
 }
 
+/// How the `*_sel` movements of the `Client`, such as `left_sel`, modify
+/// the selections.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SelectionMode {
+    /// Extend the selections, as shift+arrow does in most editors.
+    Extend,
+    /// Add a new caret. xi-core can only do so above and below the carets,
+    /// with `up_sel` and `down_sel`.
+    AddCaret,
+}
+
+impl Default for SelectionMode {
+    fn default() -> Self {
+        SelectionMode::Extend
+    }
+}
+
 #[test]
 fn serialize_ok() {
     use serde_json;
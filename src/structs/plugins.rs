@@ -13,21 +13,66 @@ pub struct AvailablePlugins {
 }
 
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct PluginStarted {
     pub view_id: ViewId,
     pub plugin: String
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct PluginStoped {
     pub view_id: ViewId,
     pub plugin: String
 }
 
+/// Whether a plugin is running for a view, as reported by the
+/// `"plugin_started"` and `"plugin_stopped"` notifications.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PluginStatus {
+    pub view_id: ViewId,
+    pub plugin: String,
+    pub running: bool,
+}
+
+impl From<PluginStarted> for PluginStatus {
+    fn from(started: PluginStarted) -> Self {
+        PluginStatus {
+            view_id: started.view_id,
+            plugin: started.plugin,
+            running: true,
+        }
+    }
+}
+
+impl From<PluginStoped> for PluginStatus {
+    fn from(stopped: PluginStoped) -> Self {
+        PluginStatus {
+            view_id: stopped.view_id,
+            plugin: stopped.plugin,
+            running: false,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct UpdateCmds {
     cmds: Vec<String>,
     plugin: String,
     view_id: ViewId
 }
+
+#[test]
+fn plugin_stopped_status() {
+    use serde_json;
+
+    let s = r#"{"view_id":"view-id-1","plugin":"syntect","code":101}"#;
+    let stopped: PluginStoped = serde_json::from_str(s).unwrap();
+    assert_eq!(
+        PluginStatus::from(stopped),
+        PluginStatus {
+            view_id: ViewId(1),
+            plugin: "syntect".into(),
+            running: false,
+        }
+    );
+}
@@ -1,5 +1,56 @@
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
-pub struct Position(pub u64, pub u64);
+/// A position in a document. Lines and columns start at 0, and columns are
+/// expressed in bytes from the start of the line, as xi-core does. Use
+/// `byte_to_utf16_col` and `utf16_to_byte_col` to convert columns from and
+/// to the UTF-16 code units of the language server protocol.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u64,
+    pub col: u64,
+}
+
+impl Position {
+    pub fn new(line: u64, col: u64) -> Self {
+        Position { line, col }
+    }
+}
+
+impl From<(u64, u64)> for Position {
+    fn from((line, col): (u64, u64)) -> Self {
+        Position { line, col }
+    }
+}
+
+impl From<Position> for (u64, u64) {
+    fn from(position: Position) -> Self {
+        (position.line, position.col)
+    }
+}
+
+/// Convert a column of `line` expressed in bytes, which is what xi-core
+/// uses, to a column expressed in UTF-16 code units, which is what the
+/// language server protocol uses. A column past the end of the line is
+/// clamped to the end of the line.
+pub fn byte_to_utf16_col(line: &str, byte_col: u64) -> u64 {
+    line.char_indices()
+        .take_while(|&(idx, _)| (idx as u64) < byte_col)
+        .map(|(_, c)| c.len_utf16() as u64)
+        .sum()
+}
+
+/// Convert a column of `line` expressed in UTF-16 code units to a column
+/// expressed in bytes. This is the inverse of `byte_to_utf16_col`. A column
+/// that falls in the middle of a character is rounded up to the end of
+/// that character.
+pub fn utf16_to_byte_col(line: &str, utf16_col: u64) -> u64 {
+    let mut utf16_idx = 0;
+    for (idx, c) in line.char_indices() {
+        if utf16_idx >= utf16_col {
+            return idx as u64;
+        }
+        utf16_idx += c.len_utf16() as u64;
+    }
+    line.len() as u64
+}
 
 #[test]
 fn deserialize_ok() {
@@ -7,5 +58,27 @@ fn deserialize_ok() {
 
     let s = r#"[12, 1]"#;
     let deserialized: Result<Position, _> = serde_json::from_str(s);
-    assert_eq!(deserialized.unwrap(), Position(12, 1));
+    assert_eq!(deserialized.unwrap(), Position::new(12, 1));
+    let s = r#"{"line": 12, "col": 1}"#;
+    let deserialized: Result<Position, _> = serde_json::from_str(s);
+    assert_eq!(deserialized.unwrap(), Position::from((12, 1)));
+}
+
+#[test]
+fn utf16_columns() {
+    // "é" is 2 bytes and 1 code unit, "𝄞" is 4 bytes and 2 code units.
+    let line = "aé𝄞b";
+    assert_eq!(byte_to_utf16_col(line, 0), 0);
+    assert_eq!(byte_to_utf16_col(line, 1), 1);
+    assert_eq!(byte_to_utf16_col(line, 3), 2);
+    assert_eq!(byte_to_utf16_col(line, 7), 4);
+    assert_eq!(byte_to_utf16_col(line, 8), 5);
+    assert_eq!(byte_to_utf16_col(line, 100), 5);
+
+    assert_eq!(utf16_to_byte_col(line, 0), 0);
+    assert_eq!(utf16_to_byte_col(line, 2), 3);
+    assert_eq!(utf16_to_byte_col(line, 3), 7);
+    assert_eq!(utf16_to_byte_col(line, 4), 7);
+    assert_eq!(utf16_to_byte_col(line, 5), 8);
+    assert_eq!(utf16_to_byte_col(line, 100), 8);
 }
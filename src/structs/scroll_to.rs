@@ -1,5 +1,8 @@
 use ViewId;
 
+/// Parameters of the `"scroll_to"` notification. The core sends it when the
+/// frontend should scroll so that the given position becomes visible, for
+/// instance after a `goto_line` or a `find_next`.
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct ScrollTo {
     pub line: u64,
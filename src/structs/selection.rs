@@ -0,0 +1,29 @@
+use Position;
+
+/// A selection region. `start` is the position where the selection was
+/// started, and `end` the position of the caret, so `start` comes after
+/// `end` for a selection made backward. A region where `start` and `end`
+/// are equal is a simple caret.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct SelRegion {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl SelRegion {
+    pub fn new(start: Position, end: Position) -> Self {
+        SelRegion { start, end }
+    }
+
+    /// Create a region containing only a caret.
+    pub fn caret(position: Position) -> Self {
+        SelRegion {
+            start: position,
+            end: position,
+        }
+    }
+
+    pub fn is_caret(&self) -> bool {
+        self.start == self.end
+    }
+}
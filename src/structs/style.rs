@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 fn default_bg_color() -> u32 {
     0
 }
@@ -23,3 +25,24 @@ pub struct Style {
     #[serde(default = "default_underline")]
     pub underline: bool,
 }
+
+/// The styles defined by the core with `"def_style"` notifications,
+/// indexed by their id.
+pub type StyleMap = HashMap<u64, Style>;
+
+#[test]
+fn deserialize_ok() {
+    use serde_json;
+
+    let s = r#"{"id":3,"fg_color":4284900966,"italic":true}"#;
+    let deserialized: Result<Style, _> = serde_json::from_str(s);
+    let style = Style {
+        id: 3,
+        fg_color: Some(4284900966),
+        bg_color: 0,
+        weight: 400,
+        italic: true,
+        underline: false,
+    };
+    assert_eq!(deserialized.unwrap(), style);
+}
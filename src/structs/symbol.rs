@@ -0,0 +1,28 @@
+/// A symbol of a document, such as a function or a type, as returned by
+/// the `"document_symbols"` plugin request.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    /// Kind of the symbol, for instance `"function"` or `"struct"`.
+    pub kind: String,
+    pub line: u64,
+    /// Column of the symbol. Plugins send it in UTF-16 code units, as in
+    /// the language server protocol, and `Client::document_symbols`
+    /// converts it to the byte column expected by the `Client`'s methods.
+    pub col: u64,
+}
+
+#[test]
+fn deserialize_symbols() {
+    use serde_json;
+
+    let s = r#"[{"name":"main","kind":"function","line":3,"col":3}]"#;
+    let deserialized: Result<Vec<Symbol>, _> = serde_json::from_str(s);
+    let symbols = vec![Symbol {
+        name: "main".into(),
+        kind: "function".into(),
+        line: 3,
+        col: 3,
+    }];
+    assert_eq!(deserialized.unwrap(), symbols);
+}
@@ -0,0 +1,51 @@
+pub type ThemeSettings = ::syntect::highlighting::ThemeSettings;
+
+/// Parameters of the `"theme_changed"` notification. The core sends it
+/// after a `"set_theme"`, with the colors of the new theme, so that the
+/// frontend can draw the background, gutter, selections etc. consistently
+/// with the spans it receives in the updates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThemeChanged {
+    pub name: String,
+    pub theme: ThemeSettings
+}
+
+/// Parameters of the `"available_themes"` notification. The core sends it
+/// at startup, and again whenever the set of themes changes, for instance
+/// when a theme file is added to the configuration directory.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AvailableThemes {
+    pub themes: Vec<String>,
+}
+
+#[test]
+fn deserialize_ok() {
+    use serde_json;
+    use syntect::highlighting::Color;
+
+    let s = r#"{"name":"InspiredGitHub","theme":{
+        "foreground":{"r":50,"g":50,"b":50,"a":255},
+        "background":{"r":255,"g":255,"b":255,"a":255},
+        "caret":{"r":50,"g":50,"b":50,"a":255},
+        "selection":{"r":200,"g":222,"b":241,"a":255}}}"#;
+    let deserialized: ThemeChanged = serde_json::from_str(s).unwrap();
+    assert_eq!(deserialized.name, "InspiredGitHub");
+    assert_eq!(
+        deserialized.theme.background,
+        Some(Color { r: 255, g: 255, b: 255, a: 255 })
+    );
+    assert_eq!(
+        deserialized.theme.selection,
+        Some(Color { r: 200, g: 222, b: 241, a: 255 })
+    );
+    assert_eq!(deserialized.theme.gutter, None);
+}
+
+#[test]
+fn deserialize_available_themes() {
+    use serde_json;
+
+    let s = r#"{"themes":["InspiredGitHub","Solarized (dark)"]}"#;
+    let deserialized: AvailableThemes = serde_json::from_str(s).unwrap();
+    assert_eq!(deserialized.themes, vec!["InspiredGitHub", "Solarized (dark)"]);
+}
@@ -1,12 +1,20 @@
 use serde::{Deserialize, Deserializer};
 
+use Annotation;
+use LineCache;
 use Operation;
+use OperationType;
+use StyleMap;
+use StyledLine;
 use ViewId;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Update {
     pub rev: Option<u64>,
     pub operations: Vec<Operation>,
+    /// Annotations of the view, such as the selections and the matches of
+    /// the current search. Older versions of the core do not send them.
+    pub annotations: Vec<Annotation>,
     pub pristine: bool,
     pub view_id: ViewId,
 }
@@ -16,6 +24,8 @@ struct InnerUpdate {
     pub rev: Option<u64>,
     #[serde(rename = "ops")]
     pub operations: Vec<Operation>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
     pub pristine: bool,
 }
 
@@ -34,6 +44,7 @@ impl<'de> Deserialize<'de> for Update {
             Update {
                 rev: update.rev,
                 operations: update.operations,
+                annotations: update.annotations,
                 pristine: update.pristine,
                 view_id,
             }
@@ -41,6 +52,43 @@ impl<'de> Deserialize<'de> for Update {
     }
 }
 
+impl Update {
+    /// Apply the update to a line cache, and return the numbers of the
+    /// lines it inserted or updated, in increasing order. The other valid
+    /// lines of the cache are unchanged, although they may have moved.
+    pub fn apply_to(&self, cache: &mut LineCache) -> impl Iterator<Item = u64> {
+        let mut changed = Vec::new();
+        let mut line_nb = 0;
+        for op in &self.operations {
+            let nb_lines = match op.operation_type {
+                OperationType::Skip => continue,
+                OperationType::Copy_ | OperationType::Invalidate => {
+                    line_nb += op.nb_lines;
+                    continue;
+                }
+                OperationType::Insert => op.lines.len() as u64,
+                OperationType::Update => op.nb_lines,
+            };
+            changed.extend(line_nb..line_nb + nb_lines);
+            line_nb += nb_lines;
+        }
+        cache.update(self.clone());
+        changed.into_iter()
+    }
+
+    /// Return the lines sent with the update, in the order of its
+    /// operations, with the style ids of their spans resolved with the
+    /// styles defined by `"def_style"` notifications (see
+    /// `Client::style_map`).
+    pub fn styled_lines(&self, styles: &StyleMap) -> Vec<StyledLine> {
+        self.operations
+            .iter()
+            .flat_map(|op| op.lines.iter())
+            .map(|line| line.styled(styles))
+            .collect()
+    }
+}
+
 
 #[test]
 fn deserialize_update() {
@@ -76,6 +124,7 @@ fn deserialize_update() {
                 ],
             },
         ],
+        annotations: vec![],
         pristine: true,
         rev: None,
         view_id: FromStr::from_str("view-id-1").unwrap(),
@@ -83,3 +132,53 @@ fn deserialize_update() {
     let deserialized: Result<Update, _> = serde_json::from_str(s);
     assert_eq!(deserialized.unwrap(), update);
 }
+
+#[test]
+fn deserialize_update_with_unknown_fields() {
+    use serde_json;
+
+    // Fields added by newer versions of the core must be ignored.
+    let s = r#"{"update":{"annotations":[],"ops":[{"n":1,"op":"ins","lines":[{"text":"foo","ln":1}]}],"pristine":false},"view_id":"view-id-1"}"#;
+    let update: Update = serde_json::from_str(s).unwrap();
+    assert_eq!(update.view_id, ViewId(1));
+    assert_eq!(update.operations[0].lines[0].text, "foo");
+}
+
+#[test]
+fn deserialize_update_with_annotations() {
+    use serde_json;
+
+    let s = r#"{"update":{"annotations":[{"type":"selection","n":1,"ranges":[[0,1,0,3]],"payloads":null}],"ops":[],"pristine":true},"view_id":"view-id-1"}"#;
+    let update: Update = serde_json::from_str(s).unwrap();
+    assert_eq!(update.annotations.len(), 1);
+    assert_eq!(update.annotations[0].ty, "selection");
+    assert_eq!(update.annotations[0].ranges[0].1.col, 3);
+}
+
+#[test]
+fn apply_update_to_cache() {
+    use serde_json;
+
+    let mut cache = LineCache::default();
+    let update: Update = serde_json::from_value(json!({
+        "update": {"ops": [{"n": 3, "op": "ins", "lines": [
+            {"text": "foo\n"}, {"text": "bar\n"}, {"text": "baz"}
+        ]}], "pristine": true},
+        "view_id": "view-id-1"
+    })).unwrap();
+    assert_eq!(update.apply_to(&mut cache).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+    let update: Update = serde_json::from_value(json!({
+        "update": {"ops": [
+            {"n": 1, "op": "copy"},
+            {"n": 1, "op": "skip"},
+            {"n": 1, "op": "ins", "lines": [{"text": "qux\n"}]},
+            {"n": 1, "op": "update", "lines": [{"cursor": [1]}]}
+        ], "pristine": false},
+        "view_id": "view-id-1"
+    })).unwrap();
+    assert_eq!(update.apply_to(&mut cache).collect::<Vec<_>>(), vec![1, 2]);
+    let lines: Vec<_> = cache.lines().iter().map(|line| line.text.as_str()).collect();
+    assert_eq!(lines, vec!["foo", "qux", "baz"]);
+    assert_eq!(cache.line(2).unwrap().cursor, vec![1]);
+}